@@ -1,20 +1,42 @@
 use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use bytes::Bytes;
 use futures_core::Stream;
+use futures_util::task::AtomicWaker;
 use http_body::Body as HttpBody;
 use tokio::time::Delay;
 
+use crate::pool::InFlight;
+use crate::timings::TimingsHandle;
+use crate::util::IdleTimeout;
+
 /// An asynchronous request body.
 pub struct Body {
     inner: Inner,
 }
 
 // The `Stream` trait isn't stable, so the impl isn't public.
-pub(crate) struct ImplStream(Body);
+pub(crate) struct ImplStream(Body, Option<Sent>);
+
+/// Tells a pending request once its body was sent, which is when its read
+/// timeout starts.
+///
+/// hyper only asks a body whether it ended once it writes it, so that is
+/// taken as the body having been sent.
+#[derive(Clone, Default)]
+pub(crate) struct Sent(Arc<SentInner>);
+
+#[derive(Default)]
+struct SentInner {
+    sent: AtomicBool,
+    waker: AtomicWaker,
+}
 
 enum Inner {
     Reusable(Bytes),
@@ -27,6 +49,10 @@ enum Inner {
             >,
         >,
         timeout: Option<Delay>,
+        read_timeout: IdleTimeout,
+        timings: Option<TimingsHandle>,
+        // Counts the connection as active until the body is done.
+        active: Option<InFlight>,
     },
 }
 
@@ -93,15 +119,29 @@ impl Body {
             inner: Inner::Streaming {
                 body,
                 timeout: None,
+                read_timeout: IdleTimeout::new(None),
+                timings: None,
+                active: None,
             },
         }
     }
 
-    pub(crate) fn response(body: hyper::Body, timeout: Option<Delay>) -> Body {
+    pub(crate) fn response(
+        body: hyper::Body,
+        timeout: Option<Delay>,
+        read_timeout: Option<Duration>,
+        timings: TimingsHandle,
+        active: Option<InFlight>,
+    ) -> Body {
+        // An empty body doesn't hold on to the connection.
+        let active = if body.is_end_stream() { None } else { active };
         Body {
             inner: Inner::Streaming {
                 body: Box::pin(WrapHyper(body)),
                 timeout,
+                read_timeout: IdleTimeout::new(read_timeout),
+                timings: Some(timings),
+                active,
             },
         }
     }
@@ -112,6 +152,9 @@ impl Body {
             inner: Inner::Streaming {
                 body: Box::pin(WrapHyper(body)),
                 timeout: None,
+                read_timeout: IdleTimeout::new(None),
+                timings: None,
+                active: None,
             },
         }
     }
@@ -143,7 +186,12 @@ impl Body {
     }
 
     pub(crate) fn into_stream(self) -> ImplStream {
-        ImplStream(self)
+        ImplStream(self, None)
+    }
+
+    /// A request body, which tells `sent` once it was sent.
+    pub(crate) fn into_request_stream(self, sent: Option<Sent>) -> ImplStream {
+        ImplStream(self, sent)
     }
 
     pub(crate) fn content_length(&self) -> Option<u64> {
@@ -195,6 +243,22 @@ impl fmt::Debug for Body {
     }
 }
 
+// ===== impl Sent =====
+
+impl Sent {
+    /// Whether the body was sent, waking the task once it is if not.
+    pub(crate) fn poll_sent(&self, cx: &mut Context) -> bool {
+        self.0.waker.register(cx.waker());
+        self.0.sent.load(Ordering::Acquire)
+    }
+
+    fn set(&self) {
+        if !self.0.sent.swap(true, Ordering::AcqRel) {
+            self.0.waker.wake();
+        }
+    }
+}
+
 // ===== impl ImplStream =====
 
 impl HttpBody for ImplStream {
@@ -209,14 +273,32 @@ impl HttpBody for ImplStream {
             Inner::Streaming {
                 ref mut body,
                 ref mut timeout,
+                ref mut read_timeout,
+                ref timings,
+                ref mut active,
             } => {
                 if let Some(ref mut timeout) = timeout {
                     if let Poll::Ready(()) = Pin::new(timeout).poll(cx) {
                         return Poll::Ready(Some(Err(crate::error::body(crate::error::TimedOut))));
                     }
                 }
-                futures_core::ready!(Pin::new(body).poll_data(cx))
-                    .map(|opt_chunk| opt_chunk.map(Into::into).map_err(crate::error::body))
+                let opt_chunk = match Pin::new(body).poll_data(cx) {
+                    Poll::Ready(opt_chunk) => opt_chunk,
+                    Poll::Pending => {
+                        if let Poll::Ready(()) = read_timeout.poll_elapsed(cx) {
+                            return Poll::Ready(Some(Err(crate::error::body(crate::error::TimedOut))));
+                        }
+                        return Poll::Pending;
+                    }
+                };
+                read_timeout.reset();
+                if opt_chunk.is_none() {
+                    if let Some(timings) = timings {
+                        timings.body_complete();
+                    }
+                    active.take();
+                }
+                opt_chunk.map(|opt_chunk| opt_chunk.map(Into::into).map_err(crate::error::body))
             }
             Inner::Reusable(ref mut bytes) => {
                 if bytes.is_empty() {
@@ -227,6 +309,11 @@ impl HttpBody for ImplStream {
             }
         };
 
+        if opt_try_chunk.is_none() {
+            if let Some(ref sent) = self.1 {
+                sent.set();
+            }
+        }
         Poll::Ready(opt_try_chunk)
     }
 
@@ -238,10 +325,16 @@ impl HttpBody for ImplStream {
     }
 
     fn is_end_stream(&self) -> bool {
-        match self.0.inner {
+        let end = match self.0.inner {
             Inner::Streaming { ref body, .. } => body.is_end_stream(),
             Inner::Reusable(ref bytes) => bytes.is_empty(),
+        };
+        if end {
+            if let Some(ref sent) = self.1 {
+                sent.set();
+            }
         }
+        end
     }
 
     fn size_hint(&self) -> http_body::SizeHint {
@@ -3,21 +3,27 @@
     feature = "rustls-tls",
 ))]
 use std::any::Any;
+use std::collections::HashMap;
 use std::convert::TryInto;
+#[cfg(feature = "__tls")]
+use std::error::Error as StdError;
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
 use std::net::IpAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 #[cfg(feature = "cookies")]
 use std::sync::RwLock;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{fmt, str};
 
 use bytes::Bytes;
 use http::header::{
-    Entry, HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH,
+    Entry, HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH,
     CONTENT_TYPE, LOCATION, PROXY_AUTHORIZATION, RANGE, REFERER, TRANSFER_ENCODING, USER_AGENT,
 };
 use http::uri::Scheme;
 use http::Uri;
+use hyper::client::connect::HttpInfo;
 use hyper::client::ResponseFuture;
 #[cfg(feature = "native-tls-crate")]
 use native_tls_crate::TlsConnector;
@@ -31,18 +37,25 @@ use log::debug;
 use super::decoder::Accepts;
 use super::request::{Request, RequestBuilder};
 use super::response::Response;
+use super::body::Sent;
 use super::Body;
-use crate::connect::{Connector, HttpConnector};
+use crate::connect::{ConnectTimings, Connector, HttpConnector, SocketConfig, SocketOptions};
 #[cfg(feature = "cookies")]
 use crate::cookie;
 use crate::error;
 use crate::into_url::{expect_uri, try_uri};
+use crate::pool::{self, ConnHandle, InFlight, PoolTracker, Ticket};
+use crate::propagation::Propagation;
+use crate::proxy::{ProxyKey, ProxyScheme};
 use crate::redirect::{self, remove_sensitive_headers};
+use crate::timings::TimingsHandle;
+use crate::trace;
+use crate::util::IdleTimeout;
 #[cfg(feature = "__tls")]
-use crate::tls::TlsBackend;
+use crate::tls::{CertificatePins, TlsBackend, TlsVerifier};
 #[cfg(feature = "__tls")]
-use crate::{Certificate, Identity};
-use crate::{IntoUrl, Method, Proxy, StatusCode, Url};
+use crate::{Certificate, HostTlsConfig, Identity, ServerCertInfo, TlsSessionCache, TlsVersion};
+use crate::{IntoUrl, IpFamily, Method, PoolStats, Proxy, ProxyProtocol, StatusCode, Url};
 
 /// An asynchronous `Client` to make Requests with.
 ///
@@ -79,22 +92,61 @@ struct Config {
     connection_verbose: bool,
     pool_idle_timeout: Option<Duration>,
     pool_max_idle_per_host: usize,
+    pool_max_connections_per_host: Option<usize>,
+    pool_max_connections: Option<usize>,
     #[cfg(feature = "__tls")]
     identity: Option<Identity>,
     proxies: Vec<Proxy>,
     auto_sys_proxy: bool,
+    proxy_failover: Option<Duration>,
+    proxy_protocol: Option<ProxyProtocol>,
+    propagation: Option<Propagation>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
     redirect_policy: redirect::Policy,
     referer: bool,
     timeout: Option<Duration>,
     #[cfg(feature = "__tls")]
     root_certs: Vec<Certificate>,
     #[cfg(feature = "__tls")]
+    tls_built_in_root_certs: bool,
+    #[cfg(feature = "__tls")]
+    certificate_pins: CertificatePins,
+    #[cfg(feature = "__tls")]
+    tls_verifier: Option<Arc<TlsVerifier>>,
+    #[cfg(feature = "__tls")]
+    host_tls: Vec<(String, HostTlsConfig)>,
+    #[cfg(feature = "__tls")]
+    tls_key_log: bool,
+    #[cfg(feature = "__tls")]
+    tls_sni: bool,
+    #[cfg(feature = "__tls")]
+    tls_session_cache: Option<TlsSessionCache>,
+    #[cfg(feature = "__tls")]
+    tls_session_resumption: bool,
+    #[cfg(feature = "__tls")]
+    tls_early_data: bool,
+    #[cfg(feature = "__tls")]
+    tls_info: bool,
+    #[cfg(feature = "__tls")]
     tls: TlsBackend,
-    http2_only: bool,
+    #[cfg(feature = "__tls")]
+    min_tls_version: Option<TlsVersion>,
+    #[cfg(feature = "__tls")]
+    max_tls_version: Option<TlsVersion>,
+    http_version_pref: HttpVersionPref,
     http1_title_case_headers: bool,
     http2_initial_stream_window_size: Option<u32>,
     http2_initial_connection_window_size: Option<u32>,
     local_address: Option<IpAddr>,
+    ip_family: Option<IpFamily>,
+    happy_eyeballs_timeout: Option<Duration>,
+    #[cfg(target_os = "linux")]
+    interface: Option<CString>,
+    tcp_keepalive: Option<Duration>,
+    tcp_keepalive_interval: Option<Duration>,
+    tcp_keepalive_retries: Option<u32>,
+    socket_config: Option<Arc<SocketConfig>>,
     nodelay: bool,
     #[cfg(feature = "cookies")]
     cookie_store: Option<cookie::CookieStore>,
@@ -102,6 +154,14 @@ struct Config {
     error: Option<crate::Error>,
 }
 
+// The HTTP versions set with `http1_only` or `http2_prior_knowledge`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HttpVersionPref {
+    Http1,
+    Http2,
+    All,
+}
+
 impl Default for ClientBuilder {
     fn default() -> Self {
         Self::new()
@@ -129,22 +189,61 @@ impl ClientBuilder {
                 connection_verbose: false,
                 pool_idle_timeout: Some(Duration::from_secs(90)),
                 pool_max_idle_per_host: std::usize::MAX,
+                pool_max_connections_per_host: None,
+                pool_max_connections: None,
                 proxies: Vec::new(),
                 auto_sys_proxy: true,
+                proxy_failover: None,
+                proxy_protocol: None,
+                propagation: None,
+                read_timeout: None,
+                write_timeout: None,
                 redirect_policy: redirect::Policy::default(),
                 referer: true,
                 timeout: None,
                 #[cfg(feature = "__tls")]
                 root_certs: Vec::new(),
                 #[cfg(feature = "__tls")]
+                tls_built_in_root_certs: true,
+                #[cfg(feature = "__tls")]
+                certificate_pins: CertificatePins::default(),
+                #[cfg(feature = "__tls")]
+                tls_verifier: None,
+                #[cfg(feature = "__tls")]
+                host_tls: Vec::new(),
+                #[cfg(feature = "__tls")]
+                tls_key_log: false,
+                #[cfg(feature = "__tls")]
+                tls_sni: true,
+                #[cfg(feature = "__tls")]
+                tls_session_cache: None,
+                #[cfg(feature = "__tls")]
+                tls_session_resumption: true,
+                #[cfg(feature = "__tls")]
+                tls_early_data: false,
+                #[cfg(feature = "__tls")]
+                tls_info: false,
+                #[cfg(feature = "__tls")]
                 identity: None,
                 #[cfg(feature = "__tls")]
                 tls: TlsBackend::default(),
-                http2_only: false,
+                #[cfg(feature = "__tls")]
+                min_tls_version: None,
+                #[cfg(feature = "__tls")]
+                max_tls_version: None,
+                http_version_pref: HttpVersionPref::All,
                 http1_title_case_headers: false,
                 http2_initial_stream_window_size: None,
                 http2_initial_connection_window_size: None,
                 local_address: None,
+                ip_family: None,
+                happy_eyeballs_timeout: Some(Duration::from_millis(300)),
+                #[cfg(target_os = "linux")]
+                interface: None,
+                tcp_keepalive: None,
+                tcp_keepalive_interval: None,
+                tcp_keepalive_retries: None,
+                socket_config: None,
                 nodelay: true,
                 trust_dns: cfg!(feature = "trust-dns"),
                 #[cfg(feature = "cookies")]
@@ -166,7 +265,7 @@ impl ClientBuilder {
             return Err(err);
         }
 
-        let mut proxies = config.proxies;
+        let mut proxies = config.proxies.clone();
         if config.auto_sys_proxy {
             proxies.push(Proxy::system());
         }
@@ -178,53 +277,196 @@ impl ClientBuilder {
                 headers.get(USER_AGENT).cloned()
             }
 
-            let http = match config.trust_dns {
-                false => HttpConnector::new_gai(),
+            // The settings of `host` take precedence over the `config` ones.
+            #[cfg(feature = "default-tls")]
+            fn native_tls_builder(
+                config: &Config,
+                host: Option<&HostTlsConfig>,
+            ) -> crate::Result<native_tls_crate::TlsConnectorBuilder> {
+                let mut tls = TlsConnector::builder();
+
+                #[cfg(feature = "native-tls")]
+                {
+                    tls.danger_accept_invalid_hostnames(!config.hostname_verification);
+                }
+
+                let certs_verification = host
+                    .and_then(|host| host.certs_verification)
+                    .unwrap_or(config.certs_verification);
+                tls.danger_accept_invalid_certs(!certs_verification);
+
+                tls.disable_built_in_roots(!config.tls_built_in_root_certs);
+
+                tls.use_sni(config.tls_sni);
+
+                match config.http_version_pref {
+                    HttpVersionPref::Http1 => tls.request_alpns(&["http/1.1"]),
+                    HttpVersionPref::Http2 => tls.request_alpns(&["h2"]),
+                    HttpVersionPref::All => tls.request_alpns(&["h2", "http/1.1"]),
+                };
+
+                let min_tls_version = host
+                    .and_then(|host| host.min_tls_version)
+                    .or(config.min_tls_version);
+                if let Some(min_tls_version) = min_tls_version {
+                    let protocol = min_tls_version.to_native_tls().ok_or_else(|| {
+                        crate::error::builder("minimum TLS version not supported by native-tls")
+                    })?;
+                    tls.min_protocol_version(Some(protocol));
+                }
+
+                let max_tls_version = host
+                    .and_then(|host| host.max_tls_version)
+                    .or(config.max_tls_version);
+                // Without a native-tls protocol for TLS 1.3, a maximum of
+                // TLS 1.3 is the same as no maximum.
+                if let Some(max_tls_version) = max_tls_version {
+                    tls.max_protocol_version(max_tls_version.to_native_tls());
+                }
+
+                let host_certs = host.map(|host| &host.root_certs[..]).unwrap_or(&[]);
+                for cert in config.root_certs.iter().chain(host_certs) {
+                    cert.clone().add_to_native_tls(&mut tls);
+                }
+
+                #[cfg(feature = "native-tls")]
+                {
+                    let identity = host
+                        .and_then(|host| host.identity.as_ref())
+                        .or(config.identity.as_ref());
+                    if let Some(id) = identity {
+                        id.clone().add_to_native_tls(&mut tls)?;
+                    }
+                }
+
+                Ok(tls)
+            }
+
+            // The settings of `host` take precedence over the `config` ones.
+            #[cfg(feature = "rustls-tls")]
+            fn rustls_config(
+                config: &Config,
+                host: Option<&HostTlsConfig>,
+            ) -> crate::Result<rustls::ClientConfig> {
+                use crate::tls::{CallbackVerifier, NoVerifier};
+
+                let mut tls = rustls::ClientConfig::new();
+                match config.http_version_pref {
+                    HttpVersionPref::Http1 => tls.set_protocols(&["http/1.1".into()]),
+                    HttpVersionPref::Http2 => tls.set_protocols(&["h2".into()]),
+                    HttpVersionPref::All => tls.set_protocols(&["h2".into(), "http/1.1".into()]),
+                }
+                tls.enable_sni = config.tls_sni;
+                if config.tls_built_in_root_certs {
+                    #[cfg(feature = "rustls-tls-native-roots")]
+                    crate::tls::add_native_roots(&mut tls.root_store)?;
+                    #[cfg(not(feature = "rustls-tls-native-roots"))]
+                    tls.root_store
+                        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+                }
+
+                let certs_verification = host
+                    .and_then(|host| host.certs_verification)
+                    .unwrap_or(config.certs_verification);
+                // The `tls_verifier` decides even when verification is off,
+                // it is only told the certificate wasn't verified.
+                if let Some(ref verifier) = config.tls_verifier {
+                    let verifier = CallbackVerifier::new(verifier.clone(), certs_verification);
+                    tls.dangerous().set_certificate_verifier(Arc::new(verifier));
+                } else if !certs_verification {
+                    tls.dangerous()
+                        .set_certificate_verifier(Arc::new(NoVerifier));
+                }
+
+                let min_tls_version = host
+                    .and_then(|host| host.min_tls_version)
+                    .or(config.min_tls_version);
+                let max_tls_version = host
+                    .and_then(|host| host.max_tls_version)
+                    .or(config.max_tls_version);
+                if min_tls_version.is_some() || max_tls_version.is_some() {
+                    let min = min_tls_version.unwrap_or(TlsVersion::Tls1_0);
+                    let max = max_tls_version.unwrap_or(TlsVersion::Tls1_3);
+                    tls.versions = [TlsVersion::Tls1_3, TlsVersion::Tls1_2]
+                        .iter()
+                        .filter(|&&version| min <= version && version <= max)
+                        .filter_map(|version| version.to_rustls())
+                        .collect();
+                    if tls.versions.is_empty() {
+                        return Err(crate::error::builder(
+                            "TLS version bounds not supported by rustls"
+                        ));
+                    }
+                }
+
+                let host_certs = host.map(|host| &host.root_certs[..]).unwrap_or(&[]);
+                for cert in config.root_certs.iter().chain(host_certs) {
+                    cert.clone().add_to_rustls(&mut tls)?;
+                }
+
+                let identity = host
+                    .and_then(|host| host.identity.as_ref())
+                    .or(config.identity.as_ref());
+                if let Some(id) = identity {
+                    id.clone().add_to_rustls(&mut tls)?;
+                }
+
+                Ok(tls)
+            }
+
+            let mut http = match config.trust_dns {
+                false => HttpConnector::new_gai(config.ip_family),
                 #[cfg(feature = "trust-dns")]
-                true => HttpConnector::new_trust_dns()?,
+                true => HttpConnector::new_trust_dns(config.ip_family)?,
                 #[cfg(not(feature = "trust-dns"))]
                 true => unreachable!("trust-dns shouldn't be enabled unless the feature is"),
             };
+            http.set_happy_eyeballs_timeout(config.happy_eyeballs_timeout);
+            http.set_keepalive(config.tcp_keepalive);
 
             #[cfg(feature = "__tls")]
-            match config.tls {
+            match &config.tls {
                 #[cfg(feature = "default-tls")]
                 TlsBackend::Default => {
-                    let mut tls = TlsConnector::builder();
-
-                    #[cfg(feature = "native-tls")]
-                    {
-                        tls.danger_accept_invalid_hostnames(!config.hostname_verification);
+                    if config.tls_key_log {
+                        log::warn!("tls_key_log is not supported by native-tls");
                     }
-
-                    tls.danger_accept_invalid_certs(!config.certs_verification);
-
-                    for cert in config.root_certs {
-                        cert.add_to_native_tls(&mut tls);
+                    if config.tls_session_cache.is_some() {
+                        log::warn!("tls_session_cache is not supported by native-tls");
                     }
-
-                    #[cfg(feature = "native-tls")]
-                    {
-                        if let Some(id) = config.identity {
-                            id.add_to_native_tls(&mut tls)?;
-                        }
+                    if config.tls_early_data {
+                        log::warn!("tls_early_data is not supported by native-tls");
                     }
 
-
-                    Connector::new_default_tls(
+                    let host_tls = config
+                        .host_tls
+                        .iter()
+                        .map(|(pattern, host)| {
+                            let tls = native_tls_builder(&config, Some(host))?
+                                .build()
+                                .map_err(crate::error::builder)?;
+                            let verified =
+                                host.certs_verification.unwrap_or(config.certs_verification);
+                            Ok((pattern.clone(), (tls, verified)))
+                        })
+                        .collect::<crate::Result<_>>()?;
+
+                    let mut connector = Connector::new_default_tls(
                         http,
-                        tls,
+                        native_tls_builder(&config, None)?,
                         proxies.clone(),
                         user_agent(&config.headers),
                         config.local_address,
                         config.nodelay,
-                    )?
+                    )?;
+                    connector.set_native_host_tls(host_tls);
+                    connector
                 },
                 #[cfg(feature = "native-tls")]
                 TlsBackend::BuiltNativeTls(conn) => {
                     Connector::from_built_default_tls(
                         http,
-                        conn,
+                        conn.clone(),
                         proxies.clone(),
                         user_agent(&config.headers),
                         config.local_address,
@@ -234,7 +476,7 @@ impl ClientBuilder {
                 TlsBackend::BuiltRustls(conn) => {
                     Connector::new_rustls_tls(
                         http,
-                        conn,
+                        conn.clone(),
                         proxies.clone(),
                         user_agent(&config.headers),
                         config.local_address,
@@ -242,38 +484,49 @@ impl ClientBuilder {
                 },
                 #[cfg(feature = "rustls-tls")]
                 TlsBackend::Rustls => {
-                    use crate::tls::NoVerifier;
-
-                    let mut tls = rustls::ClientConfig::new();
-                    if config.http2_only {
-                        tls.set_protocols(&["h2".into()]);
+                    // One key log file and session cache, shared by all of
+                    // the configs.
+                    let key_log = if config.tls_key_log {
+                        Some(Arc::new(rustls::KeyLogFile::new()))
                     } else {
-                        tls.set_protocols(&["h2".into(), "http/1.1".into()]);
-                    }
-                    tls.root_store
-                        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
-
-                    if !config.certs_verification {
-                        tls.dangerous()
-                            .set_certificate_verifier(Arc::new(NoVerifier));
-                    }
-
-                    for cert in config.root_certs {
-                        cert.add_to_rustls(&mut tls)?;
-                    }
-
-                    if let Some(id) = config.identity {
-                        id.add_to_rustls(&mut tls)?;
-                    }
-
-                    Connector::new_rustls_tls(
+                        None
+                    };
+                    let sessions: Arc<dyn rustls::StoresClientSessions> =
+                        if !config.tls_session_resumption {
+                            Arc::new(rustls::NoClientSessionStorage {})
+                        } else if let Some(ref cache) = config.tls_session_cache {
+                            cache.sessions.clone()
+                        } else {
+                            rustls::ClientSessionMemoryCache::new(32)
+                        };
+                    let with_shared = |host| {
+                        let mut tls = rustls_config(&config, host)?;
+                        if let Some(ref key_log) = key_log {
+                            tls.key_log = key_log.clone();
+                        }
+                        tls.session_persistence = sessions.clone();
+                        tls.enable_tickets = config.tls_session_resumption;
+                        Ok::<_, crate::Error>(tls)
+                    };
+
+                    let host_tls = config
+                        .host_tls
+                        .iter()
+                        .map(|(pattern, host)| {
+                            Ok((pattern.clone(), Arc::new(with_shared(Some(host))?)))
+                        })
+                        .collect::<crate::Result<_>>()?;
+
+                    let mut connector = Connector::new_rustls_tls(
                         http,
-                        tls,
+                        with_shared(None)?,
                         proxies.clone(),
                         user_agent(&config.headers),
                         config.local_address,
                         config.nodelay,
-                    )
+                    );
+                    connector.set_rustls_host_tls(host_tls);
+                    connector
                 },
                 #[cfg(any(
                     feature = "native-tls",
@@ -291,10 +544,37 @@ impl ClientBuilder {
         };
 
         connector.set_timeout(config.connect_timeout);
+        connector.set_write_timeout(config.write_timeout);
         connector.set_verbose(config.connection_verbose);
+        connector.set_proxy_failover(config.proxy_failover);
+        connector.set_proxy_protocol(config.proxy_protocol);
+        #[cfg(feature = "__tls")]
+        connector.set_certificate_pins(config.certificate_pins);
+        #[cfg(feature = "default-tls")]
+        connector.set_tls_verifier(config.tls_verifier, config.certs_verification);
+        #[cfg(feature = "__tls")]
+        connector.set_tls_info(config.tls_info);
+        connector.set_socket_options(SocketOptions {
+            #[cfg(target_os = "linux")]
+            interface: config.interface,
+            keepalive: config.tcp_keepalive,
+            keepalive_interval: config.tcp_keepalive_interval,
+            keepalive_retries: config.tcp_keepalive_retries,
+            config: config.socket_config,
+            local_address: config.local_address,
+            nodelay: config.nodelay,
+            happy_eyeballs_timeout: config.happy_eyeballs_timeout,
+        });
+
+        let pool = PoolTracker::new(
+            config.pool_max_connections_per_host,
+            config.pool_max_connections,
+        );
+        connector.set_pool(pool.clone());
+        connector.set_pool_idle_timeout(config.pool_idle_timeout);
 
         let mut builder = hyper::Client::builder();
-        if config.http2_only {
+        if config.http_version_pref == HttpVersionPref::Http2 {
             builder.http2_only(true);
         }
 
@@ -314,22 +594,39 @@ impl ClientBuilder {
             builder.http1_title_case_headers(true);
         }
 
-        let hyper_client = builder.build(connector);
+        let hyper_client = builder.clone().build(connector.clone());
 
         let proxies_maybe_http_auth = proxies.iter().any(|p| p.maybe_has_http_auth());
 
+        // Only rustls sends early data.
+        #[cfg(feature = "rustls-tls")]
+        let tls_early_data = match config.tls {
+            TlsBackend::Rustls => config.tls_early_data,
+            _ => false,
+        };
+        #[cfg(not(feature = "rustls-tls"))]
+        let tls_early_data = false;
+
         Ok(Client {
             inner: Arc::new(ClientRef {
                 accepts: config.accepts,
                 #[cfg(feature = "cookies")]
                 cookie_store: config.cookie_store.map(RwLock::new),
-                hyper: hyper_client,
+                hyper: Mutex::new(hyper_client),
+                hyper_builder: builder,
+                connector,
+                proxied: Mutex::new(HashMap::new()),
+                pool_idle_timeout: config.pool_idle_timeout,
+                tls_early_data,
                 headers: config.headers,
                 redirect_policy: config.redirect_policy,
                 referer: config.referer,
+                propagation: config.propagation,
                 request_timeout: config.timeout,
+                read_timeout: config.read_timeout,
                 proxies,
                 proxies_maybe_http_auth,
+                pool,
             }),
         })
     }
@@ -533,6 +830,16 @@ impl ClientBuilder {
         self
     }
 
+    // Tracing options
+
+    /// Inject trace context headers into every request and redirect hop.
+    ///
+    /// See the [`propagation`](crate::propagation) module for details.
+    pub fn propagation(mut self, propagation: Propagation) -> ClientBuilder {
+        self.config.propagation = Some(propagation);
+        self
+    }
+
     // Proxy options
 
     /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -561,6 +868,43 @@ impl ClientBuilder {
         self
     }
 
+    /// Fail over to the next matching `Proxy` when connecting to a proxy fails.
+    ///
+    /// By default, only the first `Proxy` that intercepts a request is used.
+    /// With failover enabled, every `Proxy` that intercepts the request is
+    /// tried in the order they were added, until a connection succeeds. A
+    /// proxy that failed is marked unhealthy and is tried last until the
+    /// `cooldown` has passed.
+    ///
+    /// The connect timeout applies to each attempt separately.
+    /// [`Response::proxy_url`](crate::Response::proxy_url) tells which proxy
+    /// was used.
+    ///
+    /// # Note
+    ///
+    /// For plain HTTP requests, the `Proxy-Authorization` header is taken
+    /// from the first proxy to try before connecting, so such a request only
+    /// fails over to the proxies with the same credentials.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn run() -> Result<(), reqwest::Error> {
+    /// use std::time::Duration;
+    ///
+    /// let client = reqwest::Client::builder()
+    ///     .proxy(reqwest::Proxy::all("http://primary.prox")?)
+    ///     .proxy(reqwest::Proxy::all("http://secondary.prox")?)
+    ///     .proxy_failover(Duration::from_secs(30))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy_failover(mut self, cooldown: Duration) -> ClientBuilder {
+        self.config.proxy_failover = Some(cooldown);
+        self
+    }
+
     // Timeout options
 
     /// Enables a request timeout.
@@ -587,6 +931,36 @@ impl ClientBuilder {
         self
     }
 
+    /// Set a timeout for reading from a connection.
+    ///
+    /// Unlike `timeout`, this restarts every time some data is received, so
+    /// it limits how long a connection may stall, not how long a response
+    /// takes. It applies while waiting for the response headers and while
+    /// reading the response body.
+    ///
+    /// Default is `None`.
+    ///
+    /// # Note
+    ///
+    /// The wait for the response headers is timed for each request, from
+    /// when its body was sent, so slow uploads don't time out, and neither
+    /// do idle connections in the pool.
+    pub fn read_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.config.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for writing to a connection.
+    ///
+    /// This restarts every time some data is sent, so it limits how long
+    /// sending a request may stall, not how long it takes.
+    ///
+    /// Default is `None`.
+    pub fn write_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.config.write_timeout = Some(timeout);
+        self
+    }
+
     /// Set whether connections should emit verbose logs.
     ///
     /// Enabling this option will emit [log][] messages at the `TRACE` level
@@ -625,15 +999,52 @@ impl ClientBuilder {
         self.pool_max_idle_per_host(max)
     }
 
+    /// Sets the maximum number of open connections per host.
+    ///
+    /// Requests that need a new connection over the limit are queued, in
+    /// order, until a connection is returned to the pool or closed. The time
+    /// spent queued counts toward the request `timeout`; timing out while
+    /// queued is reported by `Error::is_pool_timeout`.
+    ///
+    /// Idle connections in the pool count toward the limit until they are
+    /// closed, after `pool_idle_timeout`.
+    ///
+    /// Default is no limit.
+    pub fn pool_max_connections_per_host(mut self, max: usize) -> ClientBuilder {
+        self.config.pool_max_connections_per_host = Some(max);
+        self
+    }
+
+    /// Sets the maximum number of open connections, to all hosts.
+    ///
+    /// Requests over the limit are queued, as with
+    /// `pool_max_connections_per_host`. When idle connections take the room
+    /// a queued request needs, one to another host is closed for it.
+    ///
+    /// Default is no limit.
+    pub fn pool_max_connections(mut self, max: usize) -> ClientBuilder {
+        self.config.pool_max_connections = Some(max);
+        self
+    }
+
     /// Enable case sensitive headers.
     pub fn http1_title_case_headers(mut self) -> ClientBuilder {
         self.config.http1_title_case_headers = true;
         self
     }
 
+    /// Only use HTTP/1.
+    ///
+    /// HTTPS connections then only offer HTTP/1.1 with ALPN, for servers
+    /// with a broken HTTP/2 support.
+    pub fn http1_only(mut self) -> ClientBuilder {
+        self.config.http_version_pref = HttpVersionPref::Http1;
+        self
+    }
+
     /// Only use HTTP/2.
     pub fn http2_prior_knowledge(mut self) -> ClientBuilder {
-        self.config.http2_only = true;
+        self.config.http_version_pref = HttpVersionPref::Http2;
         self
     }
 
@@ -697,77 +1108,519 @@ impl ClientBuilder {
         self
     }
 
-    // TLS options
-
-    /// Add a custom root certificate.
+    /// Set which IP versions to connect with, when a host name resolves to
+    /// both IPv4 and IPv6 addresses.
+    ///
+    /// By default, the addresses are tried in the order of the resolver.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = reqwest::Client::builder()
+    ///     .ip_family(reqwest::IpFamily::V4Only)
+    ///     .build().unwrap();
+    /// ```
+    pub fn ip_family(mut self, family: IpFamily) -> ClientBuilder {
+        self.config.ip_family = Some(family);
+        self
+    }
+
+    /// Set how long to wait for a connection to the preferred IP version,
+    /// before also trying the other one ([Happy Eyeballs][rfc]).
+    ///
+    /// The preferred version is the one of the first resolved address, or
+    /// the one set with `ip_family`. Pass `None` to only try the other
+    /// version once every address of the preferred one failed.
+    ///
+    /// Default is 300 milliseconds.
+    ///
+    /// [rfc]: https://tools.ietf.org/html/rfc6555
+    pub fn happy_eyeballs_timeout<D>(mut self, timeout: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.config.happy_eyeballs_timeout = timeout.into();
+        self
+    }
+
+    /// Bind the sockets to a network interface, such as `"eth0"`, with
+    /// `SO_BINDTODEVICE`.
+    ///
+    /// # Optional
+    ///
+    /// This is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn interface(mut self, interface: &str) -> ClientBuilder {
+        match CString::new(interface) {
+            Ok(interface) => self.config.interface = Some(interface),
+            Err(e) => self.config.error = Some(crate::error::builder(e)),
+        }
+        self
+    }
+
+    /// Set how long a connection must be idle before TCP keepalive probes
+    /// are sent.
+    ///
+    /// Default is `None`, which doesn't enable TCP keepalive.
+    pub fn tcp_keepalive<D>(mut self, val: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.config.tcp_keepalive = val.into();
+        self
+    }
+
+    /// Set the time between TCP keepalive probes, with `TCP_KEEPINTVL`.
+    ///
+    /// Default is `None`, which leaves the system default. It only matters
+    /// when `tcp_keepalive` is set, and is ignored on systems other than
+    /// Linux, Android, macOS, iOS, FreeBSD and NetBSD.
+    pub fn tcp_keepalive_interval<D>(mut self, val: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.config.tcp_keepalive_interval = val.into();
+        self
+    }
+
+    /// Set how many unanswered TCP keepalive probes close a connection,
+    /// with `TCP_KEEPCNT`.
+    ///
+    /// Default is `None`, which leaves the system default. It only matters
+    /// when `tcp_keepalive` is set, and is ignored on systems other than
+    /// Linux, Android, macOS, iOS, FreeBSD and NetBSD.
+    pub fn tcp_keepalive_retries<C>(mut self, retries: C) -> ClientBuilder
+    where
+        C: Into<Option<u32>>,
+    {
+        self.config.tcp_keepalive_retries = retries.into();
+        self
+    }
+
+    /// Set a function that configures every new socket, before it connects.
+    ///
+    /// It is called for the sockets connecting to the servers, and to the
+    /// proxies. Returning an error fails the connection attempt.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = reqwest::Client::builder()
+    ///     .socket_config(|socket| socket.set_recv_buffer_size(1 << 20))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn socket_config<F>(mut self, config: F) -> ClientBuilder
+    where
+        F: Fn(&socket2::Socket) -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        self.config.socket_config = Some(Arc::new(config));
+        self
+    }
+
+    /// Send a PROXY protocol header at the start of every new connection.
+    ///
+    /// The header is written right after the TCP connection is established,
+    /// before any TLS handshake. When connecting through an HTTP proxy, it
+    /// is sent to the proxy. It is not sent through SOCKS proxies.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::net::SocketAddr;
+    ///
+    /// let client_addr: SocketAddr = "203.0.113.7:51234".parse().unwrap();
+    /// let client = reqwest::Client::builder()
+    ///     .proxy_protocol(reqwest::ProxyProtocol::v1(client_addr))
+    ///     .build().unwrap();
+    /// ```
+    pub fn proxy_protocol(mut self, proxy_protocol: ProxyProtocol) -> ClientBuilder {
+        self.config.proxy_protocol = Some(proxy_protocol);
+        self
+    }
+
+    // TLS options
+
+    /// Add a custom root certificate.
+    ///
+    /// This can be used to connect to a server that has a self-signed
+    /// certificate for example.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn add_root_certificate(mut self, cert: Certificate) -> ClientBuilder {
+        self.config.root_certs.push(cert);
+        self
+    }
+
+    /// Controls the use of built-in root certificates.
+    ///
+    /// Defaults to `true`. When disabled, only the certificates added with
+    /// `add_root_certificate` are trusted.
+    ///
+    /// The built-in roots are the system trust store with native-tls, and
+    /// the bundled `webpki-roots` with rustls. With the
+    /// `rustls-tls-native-roots` feature, rustls uses the system trust store
+    /// too, or the `SSL_CERT_FILE` and `SSL_CERT_DIR` environment variables
+    /// when set.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_built_in_root_certs(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_built_in_root_certs = enable;
+        self
+    }
+
+    /// Pin the public key of the servers matching `host_pattern`.
+    ///
+    /// `sha256_spki` is the base64 SHA-256 hash of the server certificate's
+    /// DER SubjectPublicKeyInfo, optionally prefixed with `sha256/`. The
+    /// `host_pattern` is a host name, or `*.` and a domain to match its
+    /// direct subdomains. A host can be pinned to several keys, such as a
+    /// backup key, and connecting succeeds if any of them matches.
+    ///
+    /// Any certificate of the chain the server presents can match, such as an
+    /// intermediate CA's, with rustls. native-tls only exposes the server's
+    /// own certificate, so with it only that certificate's key is pinned.
+    ///
+    /// The pins are checked after the TLS handshake, in addition to the
+    /// usual certificate verification, and also for connections tunneled
+    /// through a proxy. A mismatch fails the request with an error for which
+    /// `Error::is_certificate_pin` returns true, and that reports the hash the
+    /// server presented.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .pin_certificate("api.example.com", "sha256/4f3HKGirRuXXklx2asUYB0OFiAh0gg9Stp4pWIcUsKI=")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// `ClientBuilder::build` fails if `sha256_spki` isn't a base64 SHA-256
+    /// hash.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn pin_certificate(mut self, host_pattern: &str, sha256_spki: &str) -> ClientBuilder {
+        if let Err(err) = self.config.certificate_pins.add(host_pattern, sha256_spki) {
+            self.config.error = Some(err);
+        }
+        self
+    }
+
+    /// Set a callback to verify server certificates.
+    ///
+    /// The callback gets the certificate chain the server presented, the host
+    /// name, and the result of the usual verification. Returning `Ok` accepts
+    /// the certificate, and returning an error fails the connection with it.
+    /// This allows accepting a self-signed certificate for some hosts only,
+    /// or adding checks on top of the usual verification.
+    ///
+    /// The callback has the last word over `danger_accept_invalid_certs`:
+    /// with certificate validation disabled, the usual verification is
+    /// skipped and `ServerCertInfo::is_verified` returns false, but the
+    /// callback still runs, and can reject the certificate.
+    ///
+    /// With rustls, the callback decides instead of the usual verification.
+    /// With native-tls, it runs after the handshake, so it can reject but not
+    /// accept certificates native-tls rejected, and the chain only holds the
+    /// server certificate. To accept a self-signed certificate with
+    /// native-tls, also set `danger_accept_invalid_certs`, and check
+    /// `ServerCertInfo::host` and the certificate in the callback. It is not
+    /// applied to a rustls config passed to `use_preconfigured_tls`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .tls_verifier(|info: &reqwest::ServerCertInfo| {
+    ///         if info.is_verified() || info.host() == "dev.internal" {
+    ///             Ok(())
+    ///         } else {
+    ///             Err("untrusted certificate".into())
+    ///         }
+    ///     })
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_verifier<F>(mut self, verifier: F) -> ClientBuilder
+    where
+        F: Fn(&ServerCertInfo<'_>) -> Result<(), Box<dyn StdError + Send + Sync>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.config.tls_verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Use different TLS settings for the servers matching `host_pattern`.
+    ///
+    /// The `host_pattern` is a host name, or `*.` and a domain to match its
+    /// direct subdomains. When several patterns match a host, the first one
+    /// added is used. The settings apply to the TLS connection with the
+    /// server, also when tunneled through a proxy, but not to the connection
+    /// with an HTTPS proxy itself.
+    ///
+    /// They are not applied to a TLS backend passed to
+    /// `use_preconfigured_tls`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .host_tls_config(
+    ///         "*.internal.example",
+    ///         reqwest::HostTlsConfig::new().danger_accept_invalid_certs(true),
+    ///     )
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn host_tls_config(mut self, host_pattern: &str, config: HostTlsConfig) -> ClientBuilder {
+        self.config.host_tls.push((host_pattern.to_owned(), config));
+        self
+    }
+
+    /// Sets the identity to be used for client certificate authentication.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `native-tls` or `rustls-tls` feature to be
+    /// enabled.
+    #[cfg(feature = "__tls")]
+    pub fn identity(mut self, identity: Identity) -> ClientBuilder {
+        self.config.identity = Some(identity);
+        self
+    }
+
+    /// Set the minimum TLS version to negotiate.
+    ///
+    /// `ClientBuilder::build` returns an error if the TLS backend cannot
+    /// enforce it: native-tls cannot require TLS 1.3, and rustls only
+    /// supports TLS 1.2 and 1.3. It is not applied to a TLS backend passed to
+    /// `use_preconfigured_tls`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn min_tls_version(mut self, version: TlsVersion) -> ClientBuilder {
+        self.config.min_tls_version = Some(version);
+        self
+    }
+
+    /// Set the maximum TLS version to negotiate.
+    ///
+    /// `ClientBuilder::build` returns an error if the TLS backend supports no
+    /// version between the minimum and the maximum. It is not applied to a
+    /// TLS backend passed to `use_preconfigured_tls`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn max_tls_version(mut self, version: TlsVersion) -> ClientBuilder {
+        self.config.max_tls_version = Some(version);
+        self
+    }
+
+    /// Controls the use of hostname verification.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Warning
+    ///
+    /// You should think very carefully before you use this method. If
+    /// hostname verification is not used, any valid certificate for any
+    /// site will be trusted for use from any other. This introduces a
+    /// significant vulnerability to man-in-the-middle attacks.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `native-tls` feature to be enabled.
+    #[cfg(feature = "native-tls")]
+    pub fn danger_accept_invalid_hostnames(
+        mut self,
+        accept_invalid_hostname: bool,
+    ) -> ClientBuilder {
+        self.config.hostname_verification = !accept_invalid_hostname;
+        self
+    }
+
+    /// Controls the use of certificate validation.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Warning
+    ///
+    /// You should think very carefully before using this method. If
+    /// invalid certificates are trusted, *any* certificate for *any* site
+    /// will be trusted for use. This includes expired certificates. This
+    /// introduces significant vulnerabilities, and should only be used
+    /// as a last resort.
+    ///
+    /// A callback set with `tls_verifier` still runs, and can reject the
+    /// certificates.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> ClientBuilder {
+        self.config.certs_verification = !accept_invalid_certs;
+        self
+    }
+
+    /// Write the TLS session keys to the file named by the `SSLKEYLOGFILE`
+    /// environment variable, in the NSS key log format.
+    ///
+    /// This allows decrypting the traffic of the `Client` with tools such as
+    /// Wireshark, for debugging. The variable is read, and the file opened for
+    /// appending, when the `Client` is built. Nothing is written if it isn't
+    /// set.
+    ///
+    /// Only rustls supports this: native-tls has no way to get the session
+    /// keys, so with native-tls this only logs a warning. It is not applied
+    /// to a TLS backend passed to `use_preconfigured_tls`.
+    ///
+    /// # Warning
+    ///
+    /// Anyone who can read the file can decrypt the traffic. Don't enable
+    /// this outside of debugging.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_key_log(mut self) -> ClientBuilder {
+        self.config.tls_key_log = true;
+        self
+    }
+
+    /// Controls the use of TLS server name indication (SNI).
+    ///
+    /// Defaults to `true`. When disabled, the server name isn't sent in the
+    /// handshake, for servers that reject it. The server certificate is
+    /// still verified against the name. It is not applied to a TLS backend
+    /// passed to `use_preconfigured_tls`.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_sni(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_sni = enable;
+        self
+    }
+
+    /// Sets the cache of TLS sessions to resume on new connections.
     ///
-    /// This can be used to connect to a server that has a self-signed
-    /// certificate for example.
+    /// Defaults to a cache of 32 sessions for each `Client`. Passing clones
+    /// of the same `TlsSessionCache` to several builders lets their clients
+    /// resume each other's sessions.
+    ///
+    /// Only the rustls backend resumes sessions, and the cache is not
+    /// applied to a TLS backend passed to `use_preconfigured_tls`.
     ///
     /// # Optional
     ///
     /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
     /// feature to be enabled.
     #[cfg(feature = "__tls")]
-    pub fn add_root_certificate(mut self, cert: Certificate) -> ClientBuilder {
-        self.config.root_certs.push(cert);
+    pub fn tls_session_cache(mut self, cache: TlsSessionCache) -> ClientBuilder {
+        self.config.tls_session_cache = Some(cache);
         self
     }
 
-    /// Sets the identity to be used for client certificate authentication.
+    /// Controls the resumption of TLS sessions.
+    ///
+    /// Defaults to `true`. When disabled, every connection does a full
+    /// handshake, and no session tickets are asked for.
     ///
     /// # Optional
     ///
-    /// This requires the optional `native-tls` or `rustls-tls` feature to be
-    /// enabled.
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
     #[cfg(feature = "__tls")]
-    pub fn identity(mut self, identity: Identity) -> ClientBuilder {
-        self.config.identity = Some(identity);
+    pub fn tls_session_resumption(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_session_resumption = enable;
         self
     }
 
-    /// Controls the use of hostname verification.
+    /// Controls sending requests as TLS 1.3 early data (0-RTT).
     ///
-    /// Defaults to `false`.
+    /// Defaults to `false`. When enabled, the first request on a connection
+    /// that resumes a session is sent along with the handshake, saving a
+    /// round trip. As early data can be replayed by an attacker, only
+    /// idempotent requests, such as `GET` or `PUT`, are sent this way, and
+    /// they get connections of their own.
     ///
-    /// # Warning
+    /// These connections only offer HTTP/1.1, unless `http2_prior_knowledge`
+    /// is set, since the HTTP version is picked before the handshake
+    /// completes. For the same reason, no early data is sent to hosts with
+    /// certificate pins, or with `tls_info` enabled.
     ///
-    /// You should think very carefully before you use this method. If
-    /// hostname verification is not used, any valid certificate for any
-    /// site will be trusted for use from any other. This introduces a
-    /// significant vulnerability to man-in-the-middle attacks.
+    /// Only the rustls backend sends early data, and it is not applied to a
+    /// TLS backend passed to `use_preconfigured_tls`.
     ///
     /// # Optional
     ///
-    /// This requires the optional `native-tls` feature to be enabled.
-    #[cfg(feature = "native-tls")]
-    pub fn danger_accept_invalid_hostnames(
-        mut self,
-        accept_invalid_hostname: bool,
-    ) -> ClientBuilder {
-        self.config.hostname_verification = !accept_invalid_hostname;
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_early_data(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_early_data = enable;
         self
     }
 
-    /// Controls the use of certificate validation.
-    ///
-    /// Defaults to `false`.
+    /// Add `TlsInfo` to responses received over TLS.
     ///
-    /// # Warning
-    ///
-    /// You should think very carefully before using this method. If
-    /// invalid certificates are trusted, *any* certificate for *any* site
-    /// will be trusted for use. This includes expired certificates. This
-    /// introduces significant vulnerabilities, and should only be used
-    /// as a last resort.
+    /// Defaults to `false`. When enabled, `Response::tls_info` returns the
+    /// certificates the server presented and what the handshake negotiated.
     ///
     /// # Optional
     ///
     /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
     /// feature to be enabled.
     #[cfg(feature = "__tls")]
-    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> ClientBuilder {
-        self.config.certs_verification = !accept_invalid_certs;
+    pub fn tls_info(mut self, enable: bool) -> ClientBuilder {
+        self.config.tls_info = enable;
         self
     }
 
@@ -994,11 +1847,179 @@ impl Client {
         self.execute_request(request)
     }
 
+    /// Opens a connection to the host of `url`, ahead of the requests to it.
+    ///
+    /// The connection is made the way a request would make it, including
+    /// the DNS lookup, the proxies and the TLS handshake, but no request is
+    /// sent. It is then idle in the pool, until a request to the same host
+    /// needs a new connection, the server closes it, or the
+    /// `pool_idle_timeout` expires. It counts against the
+    /// `pool_max_connections_per_host` and `pool_max_connections` limits.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # async fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::new();
+    /// client.preconnect("https://hyper.rs").await?;
+    /// // The request doesn't wait for a connection.
+    /// let res = client.get("https://hyper.rs/guides").send().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the URL is invalid, or if the connection
+    /// couldn't be made.
+    pub fn preconnect<U: IntoUrl>(&self, url: U) -> impl Future<Output = crate::Result<()>> {
+        self.preconnect_n(url, 1)
+    }
+
+    /// Opens `count` connections to the host of `url` at once, ahead of the
+    /// requests to it.
+    ///
+    /// See `preconnect` for details. This fails if any of the connections
+    /// couldn't be made, but keeps the other ones.
+    pub fn preconnect_n<U: IntoUrl>(
+        &self,
+        url: U,
+        count: usize,
+    ) -> impl Future<Output = crate::Result<()>> {
+        let url = url.into_url().and_then(|url| {
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return Err(error::url_bad_scheme(url));
+            }
+            if self.inner.pool.is_shut_down() {
+                return Err(error::request("client is shut down").with_url(url));
+            }
+            Ok(url)
+        });
+        let connector = self.inner.connector.clone();
+        async move {
+            let url = url?;
+            let dst = expect_uri(&url);
+            let connects = (0..count).map(|_| connector.preconnect(dst.clone()));
+            match futures_util::future::try_join_all(connects).await {
+                Ok(_) => Ok(()),
+                Err(err) => Err(error::request(err).with_url(url)),
+            }
+        }
+    }
+
+    /// Returns the number of idle and active connections, per host.
+    ///
+    /// A connection is active while a request is waiting for its response,
+    /// or reading the response body.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.inner.pool.stats()
+    }
+
+    /// Closes the idle connections of this `Client`.
+    ///
+    /// Connections in use by a request are closed once the request is done,
+    /// instead of being returned to the pool.
+    pub fn close_idle_connections(&self) {
+        self.inner.close_idle_connections();
+    }
+
+    /// Shuts down this `Client`, and all of its clones.
+    ///
+    /// New requests fail right away. The returned future resolves once the
+    /// requests in flight are done, including reading their response bodies,
+    /// and the connections are closed.
+    ///
+    /// A `Response` whose body is never read nor dropped keeps this waiting
+    /// forever; use `shutdown_timeout` to bound the wait.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # async fn run() {
+    /// let client = reqwest::Client::new();
+    /// // ...
+    /// client.shutdown().await;
+    /// # }
+    /// ```
+    pub fn shutdown(&self) -> impl Future<Output = ()> {
+        self.inner.pool.shut_down();
+        let inner = self.inner.clone();
+        async move {
+            inner.pool.idle().await;
+            inner.close_idle_connections();
+        }
+    }
+
+    /// Shuts down this `Client` like `shutdown`, but waits for the requests
+    /// in flight for at most `timeout`.
+    ///
+    /// Resolves to `true` if every request was done in time. Either way, the
+    /// idle connections are closed, and the ones still in use are closed once
+    /// their requests are done.
+    pub fn shutdown_timeout(&self, timeout: Duration) -> impl Future<Output = bool> {
+        let shutdown = self.shutdown();
+        let inner = self.inner.clone();
+        async move {
+            let done = tokio::time::timeout(timeout, shutdown).await.is_ok();
+            if !done {
+                inner.close_idle_connections();
+            }
+            done
+        }
+    }
+
     pub(super) fn execute_request(&self, req: Request) -> Pending {
+        #[cfg(feature = "pac")]
+        {
+            if req.url().scheme() == "http" || req.url().scheme() == "https" {
+                if let Some(resolve) = self.inner.resolve_routes(req.proxies(), req.url()) {
+                    let client = self.clone();
+                    let timeout = req.timeout().copied().or(self.inner.request_timeout);
+                    return Pending::new_resolving(async move {
+                        let mut req = req;
+                        // The timeout covers resolving the routes too, and
+                        // what is left of it the rest of the request.
+                        let start = std::time::Instant::now();
+                        let resolving = tokio::task::spawn_blocking(resolve);
+                        let routes = match timeout {
+                            Some(timeout) => tokio::time::timeout(timeout, resolving)
+                                .await
+                                .map_err(|_| {
+                                    crate::error::request(crate::error::TimedOut)
+                                        .with_url(req.url().clone())
+                                })?,
+                            None => resolving.await,
+                        };
+                        let routes =
+                            routes.map_err(|e| crate::error::request(e).with_url(req.url().clone()))?;
+                        if let Some(timeout) = timeout {
+                            let left = timeout.checked_sub(start.elapsed()).unwrap_or_default();
+                            *req.timeout_mut() = Some(left);
+                        }
+                        client.execute_resolved(req, Some(routes)).await
+                    });
+                }
+            }
+        }
+
+        self.execute_resolved(req, None)
+    }
+
+    /// Sends the request, through `routes` if they were resolved for it.
+    fn execute_resolved(
+        &self,
+        mut req: Request,
+        routes: Option<Vec<Option<ProxyScheme>>>,
+    ) -> Pending {
+        let proxies = req.proxies_mut().take();
+        let tls_server_name = req.tls_server_name_mut().take();
         let (method, url, mut headers, body, timeout) = req.pieces();
         if url.scheme() != "http" && url.scheme() != "https" {
             return Pending::new_err(error::url_bad_scheme(url));
         }
+        if self.inner.pool.is_shut_down() {
+            return Pending::new_err(error::request("client is shut down").with_url(url));
+        }
 
         // insert default headers in the request headers
         // without overwriting already appended headers.
@@ -1037,65 +2058,76 @@ impl Client {
             None => (None, Body::empty()),
         };
 
-        self.proxy_auth(&uri, &mut headers);
+        let routed = routes.map(|routes| vec![Proxy::from_routes(routes)]);
+        let (proxy_auth, pinned) =
+            self.inner
+                .proxy_auth(routed.as_ref().or(proxies.as_ref()), &uri, &mut headers);
+        let hyper = self.inner.hyper_for(
+            pinned.as_ref().or(routed.as_ref()).or(proxies.as_ref()),
+            tls_server_name.as_ref(),
+            self.inner.early_data(&method, &url),
+        );
+        let pool_key = pool::key(&uri);
 
+        let sent = self.inner.read_timeout.map(|_| Sent::default());
         let mut req = hyper::Request::builder()
             .method(method.clone())
             .uri(uri)
-            .body(body.into_stream())
+            .body(body.into_request_stream(sent.clone()))
             .expect("valid request parts");
 
         let timeout = timeout
             .or(self.inner.request_timeout)
             .map(tokio::time::delay_for);
 
+        let span = span!(
+            "request",
+            method = %method,
+            url = %url,
+            status = tracing::field::Empty,
+            remote_addr = tracing::field::Empty,
+            version = tracing::field::Empty,
+        );
+        let mut propagated = Vec::new();
+        if let Some(ref propagation) = self.inner.propagation {
+            let _span = span.clone().entered();
+            propagation.inject(&mut headers, &mut propagated);
+        }
+
         *req.headers_mut() = headers.clone();
 
-        let in_flight = self.inner.hyper.request(req);
+        let in_flight = hyper.request(req);
 
         Pending {
             inner: PendingInner::Request(PendingRequest {
                 method,
                 url,
                 headers,
+                propagated,
                 body: reusable,
+                proxies,
+                proxy_auth,
+                #[cfg(feature = "pac")]
+                resolving: None,
+                tls_server_name,
 
                 urls: Vec::new(),
 
                 client: self.inner.clone(),
 
                 in_flight,
+                started: Instant::now(),
                 timeout,
+                sent,
+                read_timeout: IdleTimeout::new(self.inner.read_timeout),
+                span,
+                redirect_span: None,
+                active: Some(self.inner.pool.in_flight(&pool_key)),
+                pool_key,
+                ticket: Ticket::default(),
             }),
         }
     }
-
-    fn proxy_auth(&self, dst: &Uri, headers: &mut HeaderMap) {
-        if !self.inner.proxies_maybe_http_auth {
-            return;
-        }
-
-        // Only set the header here if the destination scheme is 'http',
-        // since otherwise, the header will be included in the CONNECT tunnel
-        // request instead.
-        if dst.scheme() != Some(&Scheme::HTTP) {
-            return;
-        }
-
-        if headers.contains_key(PROXY_AUTHORIZATION) {
-            return;
-        }
-
-        for proxy in self.inner.proxies.iter() {
-            if proxy.is_match(dst) {
-                if let Some(header) = proxy.http_basic_auth(dst) {
-                    headers.insert(PROXY_AUTHORIZATION, header);
-                }
-
-                break;
-            }
-        }
-    }
 }
 
 impl fmt::Debug for Client {
@@ -1132,6 +2164,10 @@ impl Config {
             f.field("proxies", &self.proxies);
         }
 
+        if let Some(ref d) = self.proxy_failover {
+            f.field("proxy_failover", d);
+        }
+
         if !self.redirect_policy.is_default() {
             f.field("redirect_policy", &self.redirect_policy);
         }
@@ -1140,28 +2176,89 @@ impl Config {
             f.field("referer", &true);
         }
 
+        if let Some(ref v) = self.propagation {
+            f.field("propagation", v);
+        }
+
         f.field("default_headers", &self.headers);
 
         if self.http1_title_case_headers {
             f.field("http1_title_case_headers", &true);
         }
 
-        if self.http2_only {
-            f.field("http2_prior_knowledge", &true);
+        match self.http_version_pref {
+            HttpVersionPref::Http1 => {
+                f.field("http1_only", &true);
+            }
+            HttpVersionPref::Http2 => {
+                f.field("http2_prior_knowledge", &true);
+            }
+            HttpVersionPref::All => {}
         }
 
         if let Some(ref d) = self.connect_timeout {
             f.field("connect_timeout", d);
         }
 
+        if let Some(ref v) = self.pool_max_connections_per_host {
+            f.field("pool_max_connections_per_host", v);
+        }
+
+        if let Some(ref v) = self.pool_max_connections {
+            f.field("pool_max_connections", v);
+        }
+
         if let Some(ref d) = self.timeout {
             f.field("timeout", d);
         }
 
+        if let Some(ref d) = self.read_timeout {
+            f.field("read_timeout", d);
+        }
+
+        if let Some(ref d) = self.write_timeout {
+            f.field("write_timeout", d);
+        }
+
         if let Some(ref v) = self.local_address {
             f.field("local_address", v);
         }
 
+        if let Some(ref v) = self.ip_family {
+            f.field("ip_family", v);
+        }
+
+        if self.happy_eyeballs_timeout != Some(Duration::from_millis(300)) {
+            f.field("happy_eyeballs_timeout", &self.happy_eyeballs_timeout);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(ref v) = self.interface {
+                f.field("interface", v);
+            }
+        }
+
+        if let Some(ref d) = self.tcp_keepalive {
+            f.field("tcp_keepalive", d);
+        }
+
+        if let Some(ref d) = self.tcp_keepalive_interval {
+            f.field("tcp_keepalive_interval", d);
+        }
+
+        if let Some(ref v) = self.tcp_keepalive_retries {
+            f.field("tcp_keepalive_retries", v);
+        }
+
+        if self.socket_config.is_some() {
+            f.field("socket_config", &true);
+        }
+
+        if let Some(ref v) = self.proxy_protocol {
+            f.field("proxy_protocol", v);
+        }
+
         if self.nodelay {
             f.field("tcp_nodelay", &true);
         }
@@ -1178,6 +2275,54 @@ impl Config {
             if !self.certs_verification {
                 f.field("danger_accept_invalid_certs", &true);
             }
+
+            if !self.tls_built_in_root_certs {
+                f.field("tls_built_in_root_certs", &false);
+            }
+
+            if !self.certificate_pins.is_empty() {
+                f.field("certificate_pins", &self.certificate_pins);
+            }
+
+            if self.tls_verifier.is_some() {
+                f.field("tls_verifier", &true);
+            }
+
+            if !self.host_tls.is_empty() {
+                f.field("host_tls_config", &self.host_tls);
+            }
+
+            if self.tls_key_log {
+                f.field("tls_key_log", &true);
+            }
+
+            if !self.tls_sni {
+                f.field("tls_sni", &false);
+            }
+
+            if let Some(ref cache) = self.tls_session_cache {
+                f.field("tls_session_cache", cache);
+            }
+
+            if !self.tls_session_resumption {
+                f.field("tls_session_resumption", &false);
+            }
+
+            if self.tls_early_data {
+                f.field("tls_early_data", &true);
+            }
+
+            if self.tls_info {
+                f.field("tls_info", &true);
+            }
+
+            if let Some(ref v) = self.min_tls_version {
+                f.field("min_tls_version", v);
+            }
+
+            if let Some(ref v) = self.max_tls_version {
+                f.field("max_tls_version", v);
+            }
         }
 
         #[cfg(all(feature = "native-tls-crate", feature = "rustls-tls"))]
@@ -1187,20 +2332,191 @@ impl Config {
     }
 }
 
+// The hyper clients of requests overriding the proxies are keyed by their
+// proxies, and `None` is for the `Client` proxies. Then by the TLS server
+// name overriding the URL host, and whether they send early data.
+type OverrideKey = (Option<Vec<ProxyKey>>, Option<String>, bool);
+
+// The most hyper clients kept for overriding requests, past which the least
+// recently used one is dropped.
+const MAX_OVERRIDE_CLIENTS: usize = 64;
+
 struct ClientRef {
     accepts: Accepts,
     #[cfg(feature = "cookies")]
     cookie_store: Option<RwLock<cookie::CookieStore>>,
     headers: HeaderMap,
-    hyper: HyperClient,
+    hyper: Mutex<HyperClient>,
+    // Used to build extra hyper clients for requests overriding the proxies
+    // or the TLS server name, or sending early data, so that their
+    // connections have a pool of their own.
+    hyper_builder: hyper::client::Builder,
+    connector: Connector,
+    // With when each was last used.
+    proxied: Mutex<HashMap<OverrideKey, (HyperClient, Instant)>>,
+    pool_idle_timeout: Option<Duration>,
+    tls_early_data: bool,
     redirect_policy: redirect::Policy,
     referer: bool,
+    propagation: Option<Propagation>,
     request_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
     proxies: Arc<Vec<Proxy>>,
     proxies_maybe_http_auth: bool,
+    pool: PoolTracker,
 }
 
 impl ClientRef {
+    /// Get the hyper client to send a request to `dst` with.
+    ///
+    /// When a request overrides the proxies, it goes through a hyper client
+    /// (and so a connection pool) dedicated to its list of proxies, whose
+    /// connector picks and fails over between them like the `Client`'s. The
+    /// same goes for a request overriding the TLS server name, or sending
+    /// early data.
+    ///
+    /// These hyper clients are dropped once unused for `pool_idle_timeout`,
+    /// when their pools are empty anyway, and there are at most
+    /// `MAX_OVERRIDE_CLIENTS` of them.
+    fn hyper_for(
+        &self,
+        proxies: Option<&Vec<Proxy>>,
+        tls_server_name: Option<&String>,
+        early_data: bool,
+    ) -> HyperClient {
+        if proxies.is_none() && tls_server_name.is_none() && !early_data {
+            return self.hyper.lock().unwrap().clone();
+        }
+
+        let keys = proxies.map(|proxies| proxies.iter().map(Proxy::key).collect());
+        let key = (keys, tls_server_name.cloned(), early_data);
+        let now = Instant::now();
+        let mut proxied = self.proxied.lock().unwrap();
+        if let Some(timeout) = self.pool_idle_timeout {
+            proxied.retain(|_, (_, used)| now.duration_since(*used) < timeout);
+        }
+        if !proxied.contains_key(&key) && proxied.len() >= MAX_OVERRIDE_CLIENTS {
+            let oldest = proxied
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                proxied.remove(&oldest);
+            }
+        }
+
+        let (hyper, used) = proxied
+            .entry(key.clone())
+            .or_insert_with(|| {
+                let (_, _tls_server_name, _early_data) = key;
+                let connector = match proxies {
+                    Some(proxies) => self.connector.with_proxies(Arc::new(proxies.clone())),
+                    None => self.connector.clone(),
+                };
+                #[cfg(feature = "__tls")]
+                let connector = match _tls_server_name {
+                    Some(name) => connector.with_tls_server_name(name),
+                    None => connector,
+                };
+                #[cfg(feature = "rustls-tls")]
+                let connector = if _early_data {
+                    connector.with_tls_early_data()
+                } else {
+                    connector
+                };
+                (self.hyper_builder.clone().build(connector), now)
+            });
+        *used = now;
+        hyper.clone()
+    }
+
+    /// Returns how to resolve the routes of a request whose proxies have a
+    /// PAC script, to run on the blocking pool, since the script may block.
+    ///
+    /// The whole URL is known here, unlike in the connector, so the script
+    /// can route a plain `http` request by its path.
+    #[cfg(feature = "pac")]
+    fn resolve_routes(
+        &self,
+        proxies: Option<&Vec<Proxy>>,
+        url: &Url,
+    ) -> Option<Box<dyn FnOnce() -> Vec<Option<ProxyScheme>> + Send>> {
+        let proxies = proxies.unwrap_or(&self.proxies);
+        if !crate::proxy::has_pac(proxies) {
+            return None;
+        }
+        let connector = self.connector.clone();
+        let proxies = proxies.clone();
+        let uri = expect_uri(url);
+        Some(Box::new(move || connector.routes(&proxies, &uri)))
+    }
+
+    /// Sets the `Proxy-Authorization` of a plain `http` request, which its
+    /// proxy gets in a header rather than in a `CONNECT` tunnel.
+    ///
+    /// The header is set before the connector picks a route, so the request
+    /// is pinned to the routes with the same credentials as the first one,
+    /// and the connector only fails over between those. Returns whether the
+    /// header was set, and the proxies pinning the request, if it had to be.
+    fn proxy_auth(
+        &self,
+        proxies: Option<&Vec<Proxy>>,
+        dst: &Uri,
+        headers: &mut HeaderMap,
+    ) -> (bool, Option<Vec<Proxy>>) {
+        // Only set the header here if the destination scheme is 'http',
+        // since otherwise, the header will be included in the CONNECT tunnel
+        // request instead. Credentials set on the request are kept.
+        if dst.scheme() != Some(&Scheme::HTTP) || headers.contains_key(PROXY_AUTHORIZATION) {
+            return (false, None);
+        }
+        let maybe_http_auth = match proxies {
+            Some(proxies) => proxies.iter().any(Proxy::maybe_has_http_auth),
+            None => self.proxies_maybe_http_auth,
+        };
+        if !maybe_http_auth {
+            return (false, None);
+        }
+
+        let routes = self.connector.routes(proxies.unwrap_or(&self.proxies), dst);
+        let auth = match routes.first() {
+            Some(route) => route.as_ref().and_then(ProxyScheme::http_auth).cloned(),
+            None => return (false, None),
+        };
+        let count = routes.len();
+        let routes = routes
+            .into_iter()
+            .filter(|route| route.as_ref().and_then(ProxyScheme::http_auth) == auth.as_ref())
+            .collect::<Vec<_>>();
+        let pinned = if routes.len() < count {
+            Some(vec![Proxy::from_routes(routes)])
+        } else {
+            None
+        };
+
+        match auth {
+            Some(auth) => {
+                headers.insert(PROXY_AUTHORIZATION, auth);
+                (true, pinned)
+            }
+            None => (false, pinned),
+        }
+    }
+
+    /// Whether a request is sent as TLS early data, which may be replayed,
+    /// and so is only for idempotent requests.
+    fn early_data(&self, method: &Method, url: &Url) -> bool {
+        self.tls_early_data && method.is_idempotent() && url.scheme() == "https"
+    }
+
+    /// Drops the connection pools, by replacing the hyper clients.
+    fn close_idle_connections(&self) {
+        let hyper = self.hyper_builder.clone().build(self.connector.clone());
+        *self.hyper.lock().unwrap() = hyper;
+        self.proxied.lock().unwrap().clear();
+        self.connector.close_warm();
+    }
+
     fn fmt_fields(&self, f: &mut fmt::DebugStruct<'_, '_>) {
         // Instead of deriving Debug, only print fields when their output
         // would provide relevant or interesting data.
@@ -1240,21 +2556,48 @@ pub(super) struct Pending {
 
 enum PendingInner {
     Request(PendingRequest),
+    #[cfg(feature = "pac")]
+    Resolving(Resolving),
     Error(Option<crate::Error>),
 }
 
+#[cfg(feature = "pac")]
+type Resolving = Pin<Box<dyn Future<Output = Result<Response, crate::Error>> + Send>>;
+
 struct PendingRequest {
     method: Method,
     url: Url,
     headers: HeaderMap,
+    // The trace context headers injected for the current hop.
+    propagated: Vec<HeaderName>,
     body: Option<Option<Bytes>>,
+    proxies: Option<Vec<Proxy>>,
+    // Whether the `Proxy-Authorization` was set by `ClientRef::proxy_auth`.
+    proxy_auth: bool,
+    // The routes of the next hop, while a PAC script resolves them.
+    #[cfg(feature = "pac")]
+    resolving: Option<tokio::task::JoinHandle<Vec<Option<ProxyScheme>>>>,
+    tls_server_name: Option<String>,
 
     urls: Vec<Url>,
 
     client: Arc<ClientRef>,
 
     in_flight: ResponseFuture,
+    started: Instant,
     timeout: Option<Delay>,
+    // The read timeout of the response headers starts once the request
+    // was sent, which `sent` tells when there is one.
+    sent: Option<Sent>,
+    read_timeout: IdleTimeout,
+
+    span: trace::Span,
+    redirect_span: Option<trace::Span>,
+
+    pool_key: String,
+    active: Option<InFlight>,
+    // The connections opened for the current hop.
+    ticket: Ticket,
 }
 
 impl PendingRequest {
@@ -1266,6 +2609,10 @@ impl PendingRequest {
         unsafe { Pin::map_unchecked_mut(self, |x| &mut x.timeout) }
     }
 
+    fn started(self: Pin<&mut Self>) -> &mut Instant {
+        unsafe { &mut Pin::get_unchecked_mut(self).started }
+    }
+
     fn urls(self: Pin<&mut Self>) -> &mut Vec<Url> {
         unsafe { &mut Pin::get_unchecked_mut(self).urls }
     }
@@ -1273,6 +2620,44 @@ impl PendingRequest {
     fn headers(self: Pin<&mut Self>) -> &mut HeaderMap {
         unsafe { &mut Pin::get_unchecked_mut(self).headers }
     }
+
+    /// Sends the request to the `url` it was redirected to, through `routes`
+    /// if they were resolved for it.
+    fn redirect(mut self: Pin<&mut Self>, routes: Option<Vec<Option<ProxyScheme>>>) {
+        let uri = expect_uri(&self.url);
+        let mut headers = std::mem::replace(self.as_mut().headers(), HeaderMap::new());
+        let routed = routes.map(|routes| vec![Proxy::from_routes(routes)]);
+        let (proxy_auth, pinned) =
+            self.client
+                .proxy_auth(routed.as_ref().or(self.proxies.as_ref()), &uri, &mut headers);
+        self.proxy_auth = proxy_auth;
+
+        let body = match self.body {
+            Some(Some(ref body)) => Body::reusable(body.clone()),
+            _ => Body::empty(),
+        };
+        let sent = self.client.read_timeout.map(|_| Sent::default());
+        let mut req = hyper::Request::builder()
+            .method(self.method.clone())
+            .uri(uri.clone())
+            .body(body.into_request_stream(sent.clone()))
+            .expect("valid request parts");
+        *req.headers_mut() = headers.clone();
+        std::mem::swap(self.as_mut().headers(), &mut headers);
+
+        let hyper = self.client.hyper_for(
+            pinned.as_ref().or(routed.as_ref()).or(self.proxies.as_ref()),
+            self.tls_server_name.as_ref(),
+            self.client.early_data(&self.method, &self.url),
+        );
+        self.pool_key = pool::key(&uri);
+        self.active = Some(self.client.pool.in_flight(&self.pool_key));
+        self.ticket = Ticket::default();
+        *self.as_mut().in_flight().get_mut() = hyper.request(req);
+        *self.as_mut().started() = Instant::now();
+        self.sent = sent;
+        self.read_timeout.reset();
+    }
 }
 
 impl Pending {
@@ -1282,6 +2667,16 @@ impl Pending {
         }
     }
 
+    #[cfg(feature = "pac")]
+    fn new_resolving<F>(resolving: F) -> Pending
+    where
+        F: Future<Output = Result<Response, crate::Error>> + Send + 'static,
+    {
+        Pending {
+            inner: PendingInner::Resolving(Box::pin(resolving)),
+        }
+    }
+
     fn inner(self: Pin<&mut Self>) -> Pin<&mut PendingInner> {
         unsafe { Pin::map_unchecked_mut(self, |x| &mut x.inner) }
     }
@@ -1294,6 +2689,8 @@ impl Future for Pending {
         let inner = self.inner();
         match inner.get_mut() {
             PendingInner::Request(ref mut req) => Pin::new(req).poll(cx),
+            #[cfg(feature = "pac")]
+            PendingInner::Resolving(ref mut resolving) => resolving.as_mut().poll(cx),
             PendingInner::Error(ref mut err) => Poll::Ready(Err(err
                 .take()
                 .expect("Pending error polled more than once"))),
@@ -1305,22 +2702,62 @@ impl Future for PendingRequest {
     type Output = Result<Response, crate::Error>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let _span = self.span.clone().entered();
+        let mut _redirect_span = self.redirect_span.clone().map(trace::Span::entered);
+
         if let Some(delay) = self.as_mut().timeout().as_mut().as_pin_mut() {
             if let Poll::Ready(()) = delay.poll(cx) {
-                return Poll::Ready(Err(
-                    crate::error::request(crate::error::TimedOut).with_url(self.url.clone())
-                ));
+                let err = if self.ticket.is_waiting() {
+                    crate::error::request(crate::error::PoolTimedOut)
+                } else {
+                    crate::error::request(crate::error::TimedOut)
+                };
+                return Poll::Ready(Err(err.with_url(self.url.clone())));
             }
         }
 
         loop {
-            let res = match self.as_mut().in_flight().as_mut().poll(cx) {
+            #[cfg(feature = "pac")]
+            {
+                if let Some(ref mut resolving) = self.resolving {
+                    let routes = match Pin::new(resolving).poll(cx) {
+                        Poll::Ready(Ok(routes)) => routes,
+                        Poll::Ready(Err(e)) => {
+                            return Poll::Ready(Err(
+                                crate::error::request(e).with_url(self.url.clone())
+                            ));
+                        }
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    self.resolving = None;
+                    self.as_mut().redirect(Some(routes));
+                }
+            }
+
+            let ticket = self.ticket.clone();
+            let res = match ticket.poll(|| self.as_mut().in_flight().as_mut().poll(cx)) {
                 Poll::Ready(Err(e)) => {
                     return Poll::Ready(Err(crate::error::request(e).with_url(self.url.clone())));
                 }
                 Poll::Ready(Ok(res)) => res,
-                Poll::Pending => return Poll::Pending,
+                Poll::Pending => {
+                    let this = &mut *self;
+                    if let Some(ref sent) = this.sent {
+                        if sent.poll_sent(cx) {
+                            if let Poll::Ready(()) = this.read_timeout.poll_elapsed(cx) {
+                                let err = crate::error::request(crate::error::TimedOut);
+                                return Poll::Ready(Err(err.with_url(this.url.clone())));
+                            }
+                        }
+                    }
+                    return Poll::Pending;
+                }
             };
+            if let (Some(active), Some(conn)) =
+                (self.active.as_mut(), res.extensions().get::<ConnHandle>())
+            {
+                active.bind(conn);
+            }
 
             #[cfg(feature = "cookies")]
             {
@@ -1404,22 +2841,32 @@ impl Future for PendingRequest {
                     match action {
                         redirect::ActionKind::Follow => {
                             debug!("redirecting '{}' to '{}'", self.url, loc);
+                            // Leave the previous hop before starting the next.
+                            _redirect_span = None;
+                            let redirect_span = span!(
+                                "redirect",
+                                url = %loc,
+                                status = res.status().as_u16(),
+                            );
+                            _redirect_span = Some(redirect_span.clone().entered());
+                            self.redirect_span = Some(redirect_span);
+                            if loc.host_str() != self.url.host_str() {
+                                self.tls_server_name = None;
+                            }
                             self.url = loc;
 
                             let mut headers =
                                 std::mem::replace(self.as_mut().headers(), HeaderMap::new());
 
                             remove_sensitive_headers(&mut headers, &self.url, &self.urls);
-                            let uri = expect_uri(&self.url);
-                            let body = match self.body {
-                                Some(Some(ref body)) => Body::reusable(body.clone()),
-                                _ => Body::empty(),
-                            };
-                            let mut req = hyper::Request::builder()
-                                .method(self.method.clone())
-                                .uri(uri.clone())
-                                .body(body.into_stream())
-                                .expect("valid request parts");
+                            let this = &mut *self;
+                            if let Some(ref propagation) = this.client.propagation {
+                                propagation.inject(&mut headers, &mut this.propagated);
+                            }
+                            // The next hop may go through another proxy.
+                            if self.proxy_auth {
+                                headers.remove(PROXY_AUTHORIZATION);
+                            }
 
                             // Add cookies from the cookie store.
                             #[cfg(feature = "cookies")]
@@ -1432,9 +2879,18 @@ impl Future for PendingRequest {
                                 }
                             }
 
-                            *req.headers_mut() = headers.clone();
                             std::mem::swap(self.as_mut().headers(), &mut headers);
-                            *self.as_mut().in_flight().get_mut() = self.client.hyper.request(req);
+
+                            #[cfg(feature = "pac")]
+                            {
+                                let resolve =
+                                    self.client.resolve_routes(self.proxies.as_ref(), &self.url);
+                                if let Some(resolve) = resolve {
+                                    self.resolving = Some(tokio::task::spawn_blocking(resolve));
+                                    continue;
+                                }
+                            }
+                            self.as_mut().redirect(None);
                             continue;
                         }
                         redirect::ActionKind::Stop => {
@@ -1448,11 +2904,25 @@ impl Future for PendingRequest {
             }
 
             debug!("response '{}' for {}", res.status(), self.url);
+            trace::record(&self.span, "status", res.status().as_u16());
+            trace::record(&self.span, "version", format_args!("{:?}", res.version()));
+            if let Some(info) = res.extensions().get::<HttpInfo>() {
+                trace::record(&self.span, "remote_addr", info.remote_addr());
+            }
+            let headers_received = Instant::now();
+            let timings = res
+                .extensions()
+                .get::<ConnectTimings>()
+                .map(|conn| conn.request_timings(self.started, headers_received))
+                .unwrap_or_default();
             let res = Response::new(
                 res,
                 self.url.clone(),
                 self.client.accepts,
                 self.timeout.take(),
+                self.client.read_timeout,
+                TimingsHandle::new(timings, headers_received),
+                self.active.take(),
             );
             return Poll::Ready(Ok(res));
         }
@@ -1467,6 +2937,8 @@ impl fmt::Debug for Pending {
                 .field("method", &req.method)
                 .field("url", &req.url)
                 .finish(),
+            #[cfg(feature = "pac")]
+            PendingInner::Resolving(_) => f.debug_struct("Pending").finish(),
             PendingInner::Error(ref err) => f.debug_struct("Pending").field("error", err).finish(),
         }
     }
@@ -1513,4 +2985,33 @@ mod tests {
         assert!(err.is_builder());
         assert_eq!(url_str, err.url().unwrap().as_str());
     }
+
+    #[test]
+    fn override_clients_are_bounded() {
+        let client = crate::Client::builder().no_proxy().build().unwrap();
+
+        for port in 0..super::MAX_OVERRIDE_CLIENTS + 10 {
+            let proxy = crate::Proxy::all(&format!("http://127.0.0.1:{}", 1000 + port)).unwrap();
+            client.inner.hyper_for(Some(&vec![proxy]), None, false);
+        }
+        let proxied = client.inner.proxied.lock().unwrap();
+        assert_eq!(proxied.len(), super::MAX_OVERRIDE_CLIENTS);
+    }
+
+    #[test]
+    fn override_clients_are_keyed_by_proxy_list() {
+        let client = crate::Client::builder().no_proxy().build().unwrap();
+        let primary = crate::Proxy::http("http://127.0.0.1:1000").unwrap();
+        let secondary = crate::Proxy::http("http://127.0.0.1:1001").unwrap();
+
+        let group = vec![primary.clone(), secondary.clone()];
+        client.inner.hyper_for(Some(&group), None, false);
+        client.inner.hyper_for(Some(&group.clone()), None, false);
+        assert_eq!(client.inner.proxied.lock().unwrap().len(), 1);
+
+        // the whole list counts, not just the proxy picked first
+        client.inner.hyper_for(Some(&vec![primary]), None, false);
+        client.inner.hyper_for(Some(&vec![secondary.clone(), secondary]), None, false);
+        assert_eq!(client.inner.proxied.lock().unwrap().len(), 3);
+    }
 }
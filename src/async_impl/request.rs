@@ -14,7 +14,7 @@ use super::client::{Client, Pending};
 use super::multipart;
 use super::response::Response;
 use crate::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
-use crate::{Method, Url};
+use crate::{Method, Proxy, Url};
 use http::{Request as HttpRequest, request::Parts};
 
 /// A request which can be executed with `Client::execute()`.
@@ -24,6 +24,8 @@ pub struct Request {
     headers: HeaderMap,
     body: Option<Body>,
     timeout: Option<Duration>,
+    proxies: Option<Vec<Proxy>>,
+    tls_server_name: Option<String>,
 }
 
 /// A builder to construct the properties of a `Request`.
@@ -41,7 +43,9 @@ impl Request {
             url,
             headers: HeaderMap::new(),
             body: None,
-            timeout: None
+            timeout: None,
+            proxies: None,
+            tls_server_name: None,
         }
     }
 
@@ -116,10 +120,36 @@ impl Request {
         let mut req = Request::new(self.method().clone(), self.url().clone());
         *req.timeout_mut() = self.timeout().cloned();
         *req.headers_mut() = self.headers().clone();
+        *req.proxies_mut() = self.proxies.clone();
+        *req.tls_server_name_mut() = self.tls_server_name.clone();
         req.body = body;
         Some(req)
     }
 
+    /// Get the proxies overriding the client's, if any.
+    #[cfg(any(feature = "blocking", feature = "pac"))]
+    pub(crate) fn proxies(&self) -> Option<&Vec<Proxy>> {
+        self.proxies.as_ref()
+    }
+
+    /// Get a mutable reference to the proxies overriding the client's.
+    ///
+    /// `None` means the proxies configured on the `Client` are used.
+    pub(crate) fn proxies_mut(&mut self) -> &mut Option<Vec<Proxy>> {
+        &mut self.proxies
+    }
+
+    /// Get the TLS server name overriding the URL host, if any.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn tls_server_name(&self) -> Option<&str> {
+        self.tls_server_name.as_ref().map(|name| &name[..])
+    }
+
+    /// Get a mutable reference to the TLS server name overriding the URL host.
+    pub(crate) fn tls_server_name_mut(&mut self) -> &mut Option<String> {
+        &mut self.tls_server_name
+    }
+
     pub(super) fn pieces(self) -> (Method, Url, HeaderMap, Option<Body>, Option<Duration>) {
         (self.method, self.url, self.headers, self.body, self.timeout)
     }
@@ -238,6 +268,80 @@ impl RequestBuilder {
         self
     }
 
+    /// Add a `Proxy` to the list of proxies used for this request.
+    ///
+    /// The proxies added to a request replace the ones configured on the
+    /// `Client` (including the "system" proxy), but only for this request.
+    /// Connections made through different proxies are never shared in the
+    /// connection pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::new();
+    /// let res = client
+    ///     .get("http://hyper.rs")
+    ///     .proxy(reqwest::Proxy::http("http://my.prox")?)
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy(mut self, proxy: Proxy) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            req.proxies_mut().get_or_insert_with(Vec::new).push(proxy);
+        }
+        self
+    }
+
+    /// Clear all `Proxies` for this request, so it is sent without a proxy.
+    ///
+    /// This overrides the proxies configured on the `Client`, including the
+    /// "system" proxy, but only for this request.
+    pub fn no_proxy(mut self) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.proxies_mut() = Some(Vec::new());
+        }
+        self
+    }
+
+    /// Use `name` instead of the URL host in the TLS handshake with the
+    /// server, for this request.
+    ///
+    /// The name is sent in SNI, unless disabled with `ClientBuilder::tls_sni`,
+    /// and the server certificate is verified against it. This allows
+    /// reaching a server behind a TLS terminator that routes on another name
+    /// than the URL host. It isn't used for the handshake with an HTTPS
+    /// proxy, nor after a redirect to another host. Connections made with
+    /// different server names are never shared in the connection pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::new();
+    /// let res = client
+    ///     .get("https://10.0.0.1/status")
+    ///     .tls_server_name("api.internal.example")
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_server_name(mut self, name: &str) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.tls_server_name_mut() = Some(name.to_owned());
+        }
+        self
+    }
+
     /// Sends a multipart/form-data body.
     ///
     /// ```
@@ -517,6 +621,8 @@ impl<T> TryFrom<HttpRequest<T>> for Request where T:Into<Body>{
             headers,
             body: Some(body.into()),
             timeout: None,
+            proxies: None,
+            tls_server_name: None,
         })
     }
 }
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 use std::fmt;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 use bytes::Bytes;
 use encoding_rs::{Encoding, UTF_8};
@@ -17,6 +18,12 @@ use url::Url;
 
 use super::body::Body;
 use super::decoder::{Accepts, Decoder};
+use crate::connect::{ConnectTimings, NegotiatedAlpn, ProxyUrl};
+use crate::connection_info::ConnectionInfo;
+use crate::pool::InFlight;
+use crate::timings::{Timings, TimingsHandle};
+#[cfg(feature = "__tls")]
+use crate::tls::TlsInfo;
 #[cfg(feature = "cookies")]
 use crate::cookie;
 
@@ -38,14 +45,19 @@ impl Response {
         url: Url,
         accepts: Accepts,
         timeout: Option<Delay>,
+        read_timeout: Option<Duration>,
+        timings: TimingsHandle,
+        active: Option<InFlight>,
     ) -> Response {
         let (parts, body) = res.into_parts();
         let status = parts.status;
         let version = parts.version;
-        let extensions = parts.extensions;
+        let mut extensions = parts.extensions;
+        extensions.insert(timings.clone());
 
         let mut headers = parts.headers;
-        let decoder = Decoder::detect(&mut headers, Body::response(body, timeout), accepts);
+        let body = Body::response(body, timeout, read_timeout, timings, active);
+        let decoder = Decoder::detect(&mut headers, body, accepts);
 
         Response {
             status,
@@ -119,6 +131,92 @@ impl Response {
             .map(|info| info.remote_addr())
     }
 
+    /// Get the URL of the proxy used to get this `Response`, if any.
+    ///
+    /// Credentials of the proxy are not included.
+    pub fn proxy_url(&self) -> Option<&Url> {
+        self.extensions
+            .get::<ProxyUrl>()
+            .map(|proxy| &proxy.0)
+    }
+
+    /// Get the TLS details of the connection this `Response` was received
+    /// on.
+    ///
+    /// Returns `None` unless `ClientBuilder::tls_info` is enabled and the
+    /// connection uses TLS.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `default-tls`, `native-tls`, or `rustls-tls`
+    /// feature to be enabled.
+    #[cfg(feature = "__tls")]
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.extensions.get::<TlsInfo>()
+    }
+
+    /// Get the local and remote addresses of the connection this `Response`
+    /// was received on, and how it was made.
+    ///
+    /// Returns `None` if the `Response` wasn't received by a `Client`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let res = reqwest::get("http://httpbin.org/get").await?;
+    /// if let Some(info) = res.connection_info() {
+    ///     println!("from {:?} to {:?}", info.local_addr(), info.remote_addr());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connection_info(&self) -> Option<ConnectionInfo> {
+        let conn = self.extensions.get::<ConnectTimings>()?;
+        Some(ConnectionInfo {
+            local_addr: conn.local_addr(),
+            remote_addr: self.remote_addr(),
+            reused: self
+                .extensions
+                .get::<TimingsHandle>()
+                .map(|timings| timings.get().is_reused())
+                .unwrap_or(false),
+            proxied: self.extensions.get::<ProxyUrl>().is_some(),
+            alpn_protocol: self
+                .extensions
+                .get::<NegotiatedAlpn>()
+                .map(|alpn| alpn.0.clone()),
+        })
+    }
+
+    /// Get how long each phase of this `Response` took.
+    ///
+    /// `Timings::body_complete` is only known once the body has been read
+    /// to the end, such as with repeated calls to `chunk`.
+    ///
+    /// This is `None` for responses that weren't received by a `Client`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut res = reqwest::get("http://httpbin.org/bytes/1024").await?;
+    /// while let Some(_chunk) = res.chunk().await? {}
+    ///
+    /// if let Some(timings) = res.timings() {
+    ///     println!("connect: {:?}", timings.connect());
+    ///     println!("waiting: {:?}", timings.first_byte());
+    ///     println!("download: {:?}", timings.body_complete());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn timings(&self) -> Option<Timings> {
+        self.extensions
+            .get::<TimingsHandle>()
+            .map(|timings| timings.get())
+    }
+
     // body methods
 
     /// Get the full response text.
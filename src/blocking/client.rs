@@ -4,6 +4,8 @@
 ))]
 use std::any::Any;
 use std::convert::TryInto;
+#[cfg(feature = "__tls")]
+use std::error::Error as StdError;
 use std::fmt;
 use std::future::Future;
 use std::net::IpAddr;
@@ -18,9 +20,11 @@ use tokio::sync::{mpsc, oneshot};
 use super::request::{Request, RequestBuilder};
 use super::response::Response;
 use super::wait;
-use crate::{async_impl, header, IntoUrl, Method, Proxy, redirect};
+use crate::{async_impl, header, IntoUrl, IpFamily, Method, PoolStats, Proxy, ProxyProtocol, redirect};
+use crate::propagation::Propagation;
+use crate::trace;
 #[cfg(feature = "__tls")]
-use crate::{Certificate, Identity};
+use crate::{Certificate, HostTlsConfig, Identity, ServerCertInfo, TlsSessionCache, TlsVersion};
 
 /// A `Client` to make Requests with.
 ///
@@ -230,6 +234,15 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.referer(enable))
     }
 
+    // Tracing options
+
+    /// Inject trace context headers into every request and redirect hop.
+    ///
+    /// See the [`propagation`](crate::propagation) module for details.
+    pub fn propagation(self, propagation: Propagation) -> ClientBuilder {
+        self.with_inner(move |inner| inner.propagation(propagation))
+    }
+
     // Proxy options
 
     /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -254,6 +267,17 @@ impl ClientBuilder {
         self
     }
 
+    /// Fail over to the next matching `Proxy` when connecting to a proxy fails.
+    ///
+    /// By default, only the first `Proxy` that intercepts a request is used.
+    /// With failover enabled, every `Proxy` that intercepts the request is
+    /// tried in the order they were added, until a connection succeeds. A
+    /// proxy that failed is marked unhealthy and is tried last until the
+    /// `cooldown` has passed.
+    pub fn proxy_failover(self, cooldown: Duration) -> ClientBuilder {
+        self.with_inner(move |inner| inner.proxy_failover(cooldown))
+    }
+
     // Timeout options
 
     /// Set a timeout for connect, read and write operations of a `Client`.
@@ -284,6 +308,39 @@ impl ClientBuilder {
         }
     }
 
+    /// Set a timeout for reading from a connection.
+    ///
+    /// Unlike `timeout`, this restarts every time some data is received, so
+    /// it limits how long a connection may stall, not how long a response
+    /// takes.
+    ///
+    /// Default is `None`.
+    pub fn read_timeout<T>(self, timeout: T) -> ClientBuilder
+    where
+        T: Into<Option<Duration>>,
+    {
+        match timeout.into() {
+            Some(dur) => self.with_inner(|inner| inner.read_timeout(dur)),
+            None => self,
+        }
+    }
+
+    /// Set a timeout for writing to a connection.
+    ///
+    /// This restarts every time some data is sent, so it limits how long
+    /// sending a request may stall, not how long it takes.
+    ///
+    /// Default is `None`.
+    pub fn write_timeout<T>(self, timeout: T) -> ClientBuilder
+    where
+        T: Into<Option<Duration>>,
+    {
+        match timeout.into() {
+            Some(dur) => self.with_inner(|inner| inner.write_timeout(dur)),
+            None => self,
+        }
+    }
+
     /// Set whether connections should emit verbose logs.
     ///
     /// Enabling this option will emit [log][] messages at the `TRACE` level
@@ -319,11 +376,33 @@ impl ClientBuilder {
         self.pool_max_idle_per_host(max)
     }
 
+    /// Sets the maximum number of open connections per host.
+    ///
+    /// Requests that need a new connection over the limit are queued, in
+    /// order, until a connection is returned to the pool or closed.
+    ///
+    /// Default is no limit.
+    pub fn pool_max_connections_per_host(self, max: usize) -> ClientBuilder {
+        self.with_inner(move |inner| inner.pool_max_connections_per_host(max))
+    }
+
+    /// Sets the maximum number of open connections, to all hosts.
+    ///
+    /// Default is no limit.
+    pub fn pool_max_connections(self, max: usize) -> ClientBuilder {
+        self.with_inner(move |inner| inner.pool_max_connections(max))
+    }
+
     /// Enable case sensitive headers.
     pub fn http1_title_case_headers(self) -> ClientBuilder {
         self.with_inner(|inner| inner.http1_title_case_headers())
     }
 
+    /// Only use HTTP/1.
+    pub fn http1_only(self) -> ClientBuilder {
+        self.with_inner(|inner| inner.http1_only())
+    }
+
     /// Only use HTTP/2.
     pub fn http2_prior_knowledge(self) -> ClientBuilder {
         self.with_inner(|inner| inner.http2_prior_knowledge())
@@ -382,6 +461,126 @@ impl ClientBuilder {
         self.with_inner(move |inner| inner.local_address(addr))
     }
 
+    /// Set which IP versions to connect with, when a host name resolves to
+    /// both IPv4 and IPv6 addresses.
+    ///
+    /// By default, the addresses are tried in the order of the resolver.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = reqwest::blocking::Client::builder()
+    ///     .ip_family(reqwest::IpFamily::V4Only)
+    ///     .build().unwrap();
+    /// ```
+    pub fn ip_family(self, family: IpFamily) -> ClientBuilder {
+        self.with_inner(move |inner| inner.ip_family(family))
+    }
+
+    /// Set how long to wait for a connection to the preferred IP version,
+    /// before also trying the other one ([Happy Eyeballs][rfc]).
+    ///
+    /// The preferred version is the one of the first resolved address, or
+    /// the one set with `ip_family`. Pass `None` to only try the other
+    /// version once every address of the preferred one failed.
+    ///
+    /// Default is 300 milliseconds.
+    ///
+    /// [rfc]: https://tools.ietf.org/html/rfc6555
+    pub fn happy_eyeballs_timeout<D>(self, timeout: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.with_inner(move |inner| inner.happy_eyeballs_timeout(timeout))
+    }
+
+    /// Bind the sockets to a network interface, such as `"eth0"`, with
+    /// `SO_BINDTODEVICE`.
+    ///
+    /// # Optional
+    ///
+    /// This is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn interface(self, interface: &str) -> ClientBuilder {
+        self.with_inner(move |inner| inner.interface(interface))
+    }
+
+    /// Set how long a connection must be idle before TCP keepalive probes
+    /// are sent.
+    ///
+    /// Default is `None`, which doesn't enable TCP keepalive.
+    pub fn tcp_keepalive<D>(self, val: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.with_inner(move |inner| inner.tcp_keepalive(val))
+    }
+
+    /// Set the time between TCP keepalive probes, with `TCP_KEEPINTVL`.
+    ///
+    /// Default is `None`, which leaves the system default. It only matters
+    /// when `tcp_keepalive` is set, and is ignored on systems other than
+    /// Linux, Android, macOS, iOS, FreeBSD and NetBSD.
+    pub fn tcp_keepalive_interval<D>(self, val: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.with_inner(move |inner| inner.tcp_keepalive_interval(val))
+    }
+
+    /// Set how many unanswered TCP keepalive probes close a connection,
+    /// with `TCP_KEEPCNT`.
+    ///
+    /// Default is `None`, which leaves the system default. It only matters
+    /// when `tcp_keepalive` is set, and is ignored on systems other than
+    /// Linux, Android, macOS, iOS, FreeBSD and NetBSD.
+    pub fn tcp_keepalive_retries<C>(self, retries: C) -> ClientBuilder
+    where
+        C: Into<Option<u32>>,
+    {
+        self.with_inner(move |inner| inner.tcp_keepalive_retries(retries))
+    }
+
+    /// Set a function that configures every new socket, before it connects.
+    ///
+    /// It is called for the sockets connecting to the servers, and to the
+    /// proxies. Returning an error fails the connection attempt.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = reqwest::blocking::Client::builder()
+    ///     .socket_config(|socket| socket.set_recv_buffer_size(1 << 20))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn socket_config<F>(self, config: F) -> ClientBuilder
+    where
+        F: Fn(&socket2::Socket) -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        self.with_inner(move |inner| inner.socket_config(config))
+    }
+
+    /// Send a PROXY protocol header at the start of every new connection.
+    ///
+    /// The header is written right after the TCP connection is established,
+    /// before any TLS handshake. When connecting through an HTTP proxy, it
+    /// is sent to the proxy. It is not sent through SOCKS proxies.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::net::SocketAddr;
+    ///
+    /// let client_addr: SocketAddr = "203.0.113.7:51234".parse().unwrap();
+    /// let client = reqwest::blocking::Client::builder()
+    ///     .proxy_protocol(reqwest::ProxyProtocol::v1(client_addr))
+    ///     .build().unwrap();
+    /// ```
+    pub fn proxy_protocol(self, proxy_protocol: ProxyProtocol) -> ClientBuilder {
+        self.with_inner(move |inner| inner.proxy_protocol(proxy_protocol))
+    }
+
     // TLS options
 
     /// Add a custom root certificate.
@@ -420,12 +619,111 @@ impl ClientBuilder {
         self.with_inner(move |inner| inner.add_root_certificate(cert))
     }
 
+    /// Controls the use of built-in root certificates.
+    ///
+    /// Defaults to `true`. When disabled, only the certificates added with
+    /// `add_root_certificate` are trusted.
+    #[cfg(feature = "__tls")]
+    pub fn tls_built_in_root_certs(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_built_in_root_certs(enable))
+    }
+
+    /// Pin the public key of the servers matching `host_pattern`.
+    ///
+    /// See `reqwest::ClientBuilder::pin_certificate` for the formats of the
+    /// pattern and the hash.
+    #[cfg(feature = "__tls")]
+    pub fn pin_certificate(self, host_pattern: &str, sha256_spki: &str) -> ClientBuilder {
+        self.with_inner(move |inner| inner.pin_certificate(host_pattern, sha256_spki))
+    }
+
+    /// Set a callback to verify server certificates.
+    ///
+    /// See `reqwest::ClientBuilder::tls_verifier` for how each TLS backend
+    /// uses it.
+    #[cfg(feature = "__tls")]
+    pub fn tls_verifier<F>(self, verifier: F) -> ClientBuilder
+    where
+        F: Fn(&ServerCertInfo<'_>) -> Result<(), Box<dyn StdError + Send + Sync>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.with_inner(move |inner| inner.tls_verifier(verifier))
+    }
+
+    /// Use different TLS settings for the servers matching `host_pattern`.
+    ///
+    /// See `reqwest::ClientBuilder::host_tls_config` for details.
+    #[cfg(feature = "__tls")]
+    pub fn host_tls_config(self, host_pattern: &str, config: HostTlsConfig) -> ClientBuilder {
+        self.with_inner(move |inner| inner.host_tls_config(host_pattern, config))
+    }
+
+    /// Write the TLS session keys to the file named by `SSLKEYLOGFILE`.
+    ///
+    /// See `reqwest::ClientBuilder::tls_key_log` for details.
+    #[cfg(feature = "__tls")]
+    pub fn tls_key_log(self) -> ClientBuilder {
+        self.with_inner(|inner| inner.tls_key_log())
+    }
+
+    /// Controls the use of TLS server name indication (SNI).
+    ///
+    /// Defaults to `true`.
+    #[cfg(feature = "__tls")]
+    pub fn tls_sni(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_sni(enable))
+    }
+
+    /// Sets the cache of TLS sessions to resume on new connections.
+    ///
+    /// See `reqwest::ClientBuilder::tls_session_cache` for details.
+    #[cfg(feature = "__tls")]
+    pub fn tls_session_cache(self, cache: TlsSessionCache) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_session_cache(cache))
+    }
+
+    /// Controls the resumption of TLS sessions.
+    ///
+    /// Defaults to `true`.
+    #[cfg(feature = "__tls")]
+    pub fn tls_session_resumption(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_session_resumption(enable))
+    }
+
+    /// Controls sending idempotent requests as TLS 1.3 early data (0-RTT).
+    ///
+    /// See `reqwest::ClientBuilder::tls_early_data` for details.
+    #[cfg(feature = "__tls")]
+    pub fn tls_early_data(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_early_data(enable))
+    }
+
     /// Sets the identity to be used for client certificate authentication.
     #[cfg(feature = "__tls")]
     pub fn identity(self, identity: Identity) -> ClientBuilder {
         self.with_inner(move |inner| inner.identity(identity))
     }
 
+    /// Set the minimum TLS version to negotiate.
+    ///
+    /// `ClientBuilder::build` returns an error if the TLS backend cannot
+    /// enforce it.
+    #[cfg(feature = "__tls")]
+    pub fn min_tls_version(self, version: TlsVersion) -> ClientBuilder {
+        self.with_inner(move |inner| inner.min_tls_version(version))
+    }
+
+    /// Set the maximum TLS version to negotiate.
+    ///
+    /// `ClientBuilder::build` returns an error if the TLS backend supports no
+    /// version between the minimum and the maximum.
+    #[cfg(feature = "__tls")]
+    pub fn max_tls_version(self, version: TlsVersion) -> ClientBuilder {
+        self.with_inner(move |inner| inner.max_tls_version(version))
+    }
+
     /// Controls the use of hostname verification.
     ///
     /// Defaults to `false`.
@@ -461,6 +759,15 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.danger_accept_invalid_certs(accept_invalid_certs))
     }
 
+    /// Add `TlsInfo` to responses received over TLS.
+    ///
+    /// Defaults to `false`. When enabled, `Response::tls_info` returns the
+    /// certificates the server presented and what the handshake negotiated.
+    #[cfg(feature = "__tls")]
+    pub fn tls_info(self, enable: bool) -> ClientBuilder {
+        self.with_inner(move |inner| inner.tls_info(enable))
+    }
+
     /// Force using the native TLS backend.
     ///
     /// Since multiple TLS backends can be optionally enabled, this option will
@@ -663,6 +970,90 @@ impl Client {
     pub fn execute(&self, request: Request) -> crate::Result<Response> {
         self.inner.execute_request(request)
     }
+
+    /// Opens a connection to the host of `url`, ahead of the requests to it.
+    ///
+    /// The connection is made the way a request would make it, including
+    /// the DNS lookup, the proxies and the TLS handshake, but no request is
+    /// sent. It is then idle in the pool, until a request to the same host
+    /// needs a new connection, the server closes it, or the
+    /// `pool_idle_timeout` expires. It counts against the
+    /// `pool_max_connections_per_host` and `pool_max_connections` limits.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the URL is invalid, or if the connection
+    /// couldn't be made.
+    pub fn preconnect<U: IntoUrl>(&self, url: U) -> crate::Result<()> {
+        self.preconnect_n(url, 1)
+    }
+
+    /// Opens `count` connections to the host of `url` at once, ahead of the
+    /// requests to it.
+    ///
+    /// See `preconnect` for details. This fails if any of the connections
+    /// couldn't be made, but keeps the other ones.
+    pub fn preconnect_n<U: IntoUrl>(&self, url: U, count: usize) -> crate::Result<()> {
+        let url = url.into_url()?;
+        self.inner.preconnect(self.inner.inner.client.preconnect_n(url, count))
+    }
+
+    /// Returns the number of idle and active connections, per host.
+    ///
+    /// A connection is active while a request is waiting for its response,
+    /// or reading the response body.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.inner.inner.client.pool_stats()
+    }
+
+    /// Closes the idle connections of this `Client`.
+    ///
+    /// Connections in use by a request are closed once the request is done,
+    /// instead of being returned to the pool.
+    pub fn close_idle_connections(&self) {
+        self.inner.inner.client.close_idle_connections();
+    }
+
+    /// Shuts down this `Client`, and all of its clones.
+    ///
+    /// New requests fail right away. This blocks until the requests in
+    /// flight are done, including reading their response bodies, and the
+    /// connections are closed.
+    ///
+    /// A `Response` whose body is never read nor dropped keeps this blocked
+    /// forever; use `shutdown_timeout` to bound the wait.
+    pub fn shutdown(&self) {
+        let shutdown = self.inner.inner.client.shutdown();
+        let _ = wait::timeout(
+            async move {
+                shutdown.await;
+                Ok::<(), crate::Error>(())
+            },
+            None,
+        );
+    }
+
+    /// Shuts down this `Client` like `shutdown`, but blocks for at most
+    /// `timeout`.
+    ///
+    /// Returns `true` if every request was done in time. Either way, the idle
+    /// connections are closed, and the ones still in use are closed once
+    /// their requests are done.
+    pub fn shutdown_timeout(&self, timeout: Duration) -> bool {
+        let shutdown = self.inner.inner.client.shutdown();
+        let done = wait::timeout(
+            async move {
+                shutdown.await;
+                Ok::<(), crate::Error>(())
+            },
+            Some(timeout),
+        )
+        .is_ok();
+        if !done {
+            self.close_idle_connections();
+        }
+        done
+    }
 }
 
 impl fmt::Debug for Client {
@@ -688,11 +1079,14 @@ struct ClientHandle {
 }
 
 type OneshotResponse = oneshot::Sender<crate::Result<async_impl::Response>>;
-type ThreadSender = mpsc::UnboundedSender<(async_impl::Request, OneshotResponse)>;
+type ThreadSender = mpsc::UnboundedSender<(async_impl::Request, OneshotResponse, trace::Span)>;
 
 struct InnerClientHandle {
     tx: Option<ThreadSender>,
     thread: Option<thread::JoinHandle<()>>,
+    // The client driven by the runtime thread, for the pool methods.
+    client: async_impl::Client,
+    runtime: tokio::runtime::Handle,
 }
 
 impl Drop for InnerClientHandle {
@@ -714,8 +1108,8 @@ impl ClientHandle {
     fn new(builder: ClientBuilder) -> crate::Result<ClientHandle> {
         let timeout = builder.timeout;
         let builder = builder.inner;
-        let (tx, rx) = mpsc::unbounded_channel::<(async_impl::Request, OneshotResponse)>();
-        let (spawn_tx, spawn_rx) = oneshot::channel::<crate::Result<()>>();
+        let (tx, rx) = mpsc::unbounded_channel::<(async_impl::Request, OneshotResponse, trace::Span)>();
+        let (spawn_tx, spawn_rx) = oneshot::channel::<crate::Result<(async_impl::Client, tokio::runtime::Handle)>>();
         let handle = thread::Builder::new()
             .name("reqwest-internal-sync-runtime".into())
             .spawn(move || {
@@ -740,15 +1134,20 @@ impl ClientHandle {
                         }
                         Ok(v) => v,
                     };
-                    if let Err(e) = spawn_tx.send(Ok(())) {
+                    let runtime = tokio::runtime::Handle::current();
+                    if let Err(e) = spawn_tx.send(Ok((client.clone(), runtime))) {
                         error!("Failed to communicate successful startup: {:?}", e);
                         return;
                     }
 
                     let mut rx = rx;
 
-                    while let Some((req, req_tx)) = rx.recv().await {
-                        let req_fut = client.execute(req);
+                    while let Some((req, req_tx, span)) = rx.recv().await {
+                        // Parent the request span on the span of the caller.
+                        let req_fut = {
+                            let _span = span.entered();
+                            client.execute(req)
+                        };
                         tokio::spawn(forward(req_fut, req_tx));
                     }
 
@@ -764,15 +1163,17 @@ impl ClientHandle {
             .map_err(crate::error::builder)?;
 
         // Wait for the runtime thread to start up...
-        match wait::timeout(spawn_rx, None) {
-            Ok(Ok(())) => (),
+        let (client, runtime) = match wait::timeout(spawn_rx, None) {
+            Ok(Ok(started)) => started,
             Ok(Err(err)) => return Err(err),
             Err(_canceled) => event_loop_panicked(),
-        }
+        };
 
         let inner_handle = Arc::new(InnerClientHandle {
             tx: Some(tx),
             thread: Some(handle),
+            client,
+            runtime,
         });
 
         Ok(ClientHandle {
@@ -781,6 +1182,23 @@ impl ClientHandle {
         })
     }
 
+    fn preconnect<F>(&self, preconnect: F) -> crate::Result<()>
+    where
+        F: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.inner.runtime.spawn(async move {
+            let _ = tx.send(preconnect.await);
+        });
+
+        let f = async move { rx.await.map_err(|_canceled| event_loop_panicked()) };
+        match wait::timeout(f, self.timeout.0) {
+            Ok(res) => res,
+            Err(wait::Waited::TimedOut(e)) => Err(crate::error::request(e)),
+            Err(wait::Waited::Inner(err)) => Err(err),
+        }
+    }
+
     fn execute_request(&self, req: Request) -> crate::Result<Response> {
         let (tx, rx) = oneshot::channel();
         let (req, body) = req.into_async();
@@ -791,7 +1209,7 @@ impl ClientHandle {
             .tx
             .as_ref()
             .expect("core thread exited early")
-            .send((req, tx))
+            .send((req, tx, trace::Span::current()))
             .expect("core thread panicked");
 
         let result: Result<crate::Result<async_impl::Response>, wait::Waited<crate::Error>> =
@@ -13,7 +13,7 @@ use super::body::{self, Body};
 use super::multipart;
 use super::Client;
 use crate::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
-use crate::{async_impl, Method, Url};
+use crate::{async_impl, Method, Proxy, Url};
 
 /// A request which can be executed with `Client::execute()`.
 pub struct Request {
@@ -114,6 +114,8 @@ impl Request {
         };
         let mut req = Request::new(self.method().clone(), self.url().clone());
         *req.headers_mut() = self.headers().clone();
+        *req.inner.proxies_mut() = self.inner.proxies().cloned();
+        *req.inner.tls_server_name_mut() = self.inner.tls_server_name().map(str::to_owned);
         req.body = body;
         Some(req)
     }
@@ -338,6 +340,50 @@ impl RequestBuilder {
         self
     }
 
+    /// Add a `Proxy` to the list of proxies used for this request.
+    ///
+    /// The proxies added to a request replace the ones configured on the
+    /// `Client` (including the "system" proxy), but only for this request.
+    ///
+    /// ```rust
+    /// # fn run() -> Result<(), Box<std::error::Error>> {
+    /// let client = reqwest::blocking::Client::new();
+    /// let res = client.get("http://hyper.rs")
+    ///     .proxy(reqwest::Proxy::http("http://my.prox")?)
+    ///     .send()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy(mut self, proxy: Proxy) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            req.inner.proxies_mut().get_or_insert_with(Vec::new).push(proxy);
+        }
+        self
+    }
+
+    /// Clear all `Proxies` for this request, so it is sent without a proxy.
+    ///
+    /// This overrides the proxies configured on the `Client`, including the
+    /// "system" proxy, but only for this request.
+    pub fn no_proxy(mut self) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.inner.proxies_mut() = Some(Vec::new());
+        }
+        self
+    }
+
+    /// Use `name` instead of the URL host in the TLS handshake with the
+    /// server, for this request.
+    ///
+    /// See `reqwest::RequestBuilder::tls_server_name` for details.
+    #[cfg(feature = "__tls")]
+    pub fn tls_server_name(mut self, name: &str) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.inner.tls_server_name_mut() = Some(name.to_owned());
+        }
+        self
+    }
+
     /// Modify the query string of the URL.
     ///
     /// Modifies the URL of this request, adding the parameters provided.
@@ -177,6 +177,39 @@ impl Response {
         self.inner.remote_addr()
     }
 
+    /// Get the URL of the proxy used to get this `Response`, if any.
+    ///
+    /// Credentials of the proxy are not included.
+    pub fn proxy_url(&self) -> Option<&Url> {
+        self.inner.proxy_url()
+    }
+
+    /// Get the TLS details of the connection this `Response` was received
+    /// on.
+    ///
+    /// Returns `None` unless `ClientBuilder::tls_info` is enabled and the
+    /// connection uses TLS.
+    #[cfg(feature = "__tls")]
+    pub fn tls_info(&self) -> Option<&crate::TlsInfo> {
+        self.inner.tls_info()
+    }
+
+    /// Get the local and remote addresses of the connection this `Response`
+    /// was received on, and how it was made.
+    ///
+    /// Returns `None` if the `Response` wasn't received by a `Client`.
+    pub fn connection_info(&self) -> Option<crate::ConnectionInfo> {
+        self.inner.connection_info()
+    }
+
+    /// Get how long each phase of this `Response` took.
+    ///
+    /// `Timings::body_complete` is only known once the body has been read
+    /// to the end.
+    pub fn timings(&self) -> Option<crate::Timings> {
+        self.inner.timings()
+    }
+
     /// Get the content-length of the response, if it is known.
     ///
     /// Reasons it may not be known:
@@ -1,6 +1,7 @@
 use hyper::service::Service;
 use http::uri::{Scheme, Authority};
 use http::Uri;
+use hyper::client::connect::dns::{GaiResolver, Name};
 use hyper::client::connect::{Connected, Connection};
 use tokio::io::{AsyncRead, AsyncWrite};
 #[cfg(feature = "native-tls-crate")]
@@ -10,44 +11,79 @@ use http::header::HeaderValue;
 use futures_util::future::Either;
 use bytes::{Buf, BufMut};
 
+use std::collections::HashMap;
 use std::future::Future;
 use std::io;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
-use std::sync::Arc;
-use std::task::{Context, Poll};
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
 use std::mem::MaybeUninit;
 use pin_project_lite::pin_project;
 
 #[cfg(feature = "trust-dns")]
 use crate::dns::TrustDnsResolver;
-use crate::proxy::{Proxy, ProxyScheme};
+use crate::proxy::{Proxy, ProxyHealth, ProxyScheme};
 use crate::error::BoxError;
+use crate::ip_family::IpFamily;
+use crate::pool::{self, OpenConn, PoolTracker};
+use crate::timings::Timings;
+#[cfg(feature = "__tls")]
+use crate::tls::{host_matches, CertificatePins};
+#[cfg(feature = "default-tls")]
+use crate::tls::{ServerCertInfo, TlsVerifier};
+use crate::trace;
+use crate::util::IdleTimeout;
+use crate::{ProxyProtocol, Url};
+pub(crate) use self::socket::{SocketConfig, SocketOptions};
 #[cfg(feature = "default-tls")]
 use self::native_tls_conn::NativeTlsConn;
 #[cfg(feature = "rustls-tls")]
 use self::rustls_tls_conn::RustlsTlsConn;
 
+type GaiConnector = hyper::client::HttpConnector<TimedResolver<GaiResolver>>;
+#[cfg(feature = "trust-dns")]
+type TrustDnsConnector = hyper::client::HttpConnector<TimedResolver<TrustDnsResolver>>;
+
+/// hyper's connector, along with its resolver for the sockets connected by
+/// `socket::connect`.
 #[derive(Clone)]
 pub(crate) enum HttpConnector {
-    Gai(hyper::client::HttpConnector),
+    Gai(GaiConnector, TimedResolver<GaiResolver>),
     #[cfg(feature = "trust-dns")]
-    TrustDns(hyper::client::HttpConnector<TrustDnsResolver>),
+    TrustDns(TrustDnsConnector, TimedResolver<TrustDnsResolver>),
 }
 
 impl HttpConnector {
-    pub(crate) fn new_gai() -> Self {
-        Self::Gai(hyper::client::HttpConnector::new())
+    pub(crate) fn new_gai(family: Option<IpFamily>) -> Self {
+        let resolver = TimedResolver(GaiResolver::new(), family);
+        Self::Gai(
+            hyper::client::HttpConnector::new_with_resolver(resolver.clone()),
+            resolver,
+        )
     }
 
     #[cfg(feature = "trust-dns")]
-    pub(crate) fn new_trust_dns() -> crate::Result<HttpConnector> {
-        TrustDnsResolver::new()
-            .map(hyper::client::HttpConnector::new_with_resolver)
-            .map(Self::TrustDns)
+    pub(crate) fn new_trust_dns(family: Option<IpFamily>) -> crate::Result<HttpConnector> {
+        TrustDnsResolver::new(family)
+            .map(|resolver| {
+                let resolver = TimedResolver(resolver, family);
+                Self::TrustDns(
+                    hyper::client::HttpConnector::new_with_resolver(resolver.clone()),
+                    resolver,
+                )
+            })
             .map_err(crate::error::builder)
     }
+
+    fn resolve(&mut self, name: Name) -> <TimedResolver<GaiResolver> as Service<Name>>::Future {
+        match self {
+            Self::Gai(_, resolver) => resolver.call(name),
+            #[cfg(feature = "trust-dns")]
+            Self::TrustDns(_, resolver) => resolver.call(name),
+        }
+    }
 }
 
 macro_rules! impl_http_connector {
@@ -55,11 +91,11 @@ macro_rules! impl_http_connector {
         #[allow(dead_code)]
         impl HttpConnector {
             $(
-                fn $name(&mut self, $($par_name: $par_type),*)$( -> $return)? {
+                pub(crate) fn $name(&mut self, $($par_name: $par_type),*)$( -> $return)? {
                     match self {
-                        Self::Gai(resolver) => resolver.$name($($par_name),*),
+                        Self::Gai(http, _) => http.$name($($par_name),*),
                         #[cfg(feature = "trust-dns")]
-                        Self::TrustDns(resolver) => resolver.$name($($par_name),*),
+                        Self::TrustDns(http, _) => http.$name($($par_name),*),
                     }
                 }
             )+
@@ -71,57 +107,230 @@ impl_http_connector! {
     fn set_local_address(&mut self, addr: Option<IpAddr>);
     fn enforce_http(&mut self, is_enforced: bool);
     fn set_nodelay(&mut self, nodelay: bool);
+    fn set_happy_eyeballs_timeout(&mut self, timeout: Option<Duration>);
+    fn set_keepalive(&mut self, keepalive: Option<Duration>);
 }
 
 impl Service<Uri> for HttpConnector {
-    type Response = <hyper::client::HttpConnector as Service<Uri>>::Response;
-    type Error = <hyper::client::HttpConnector as Service<Uri>>::Error;
+    type Response = <GaiConnector as Service<Uri>>::Response;
+    type Error = <GaiConnector as Service<Uri>>::Error;
     #[cfg(feature = "trust-dns")]
     type Future = Either<
-        <hyper::client::HttpConnector as Service<Uri>>::Future,
-        <hyper::client::HttpConnector<TrustDnsResolver> as Service<Uri>>::Future,
+        <GaiConnector as Service<Uri>>::Future,
+        <TrustDnsConnector as Service<Uri>>::Future,
     >;
     #[cfg(not(feature = "trust-dns"))]
     type Future = Either<
-        <hyper::client::HttpConnector as Service<Uri>>::Future,
-        <hyper::client::HttpConnector as Service<Uri>>::Future,
+        <GaiConnector as Service<Uri>>::Future,
+        <GaiConnector as Service<Uri>>::Future,
     >;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         match self {
-            Self::Gai(resolver) => resolver.poll_ready(cx),
+            Self::Gai(http, _) => http.poll_ready(cx),
             #[cfg(feature = "trust-dns")]
-            Self::TrustDns(resolver) => resolver.poll_ready(cx),
+            Self::TrustDns(http, _) => http.poll_ready(cx),
         }
     }
 
     fn call(&mut self, dst: Uri) -> Self::Future {
         match self {
-            Self::Gai(resolver) => Either::Left(resolver.call(dst)),
+            Self::Gai(http, _) => Either::Left(http.call(dst)),
             #[cfg(feature = "trust-dns")]
-            Self::TrustDns(resolver) => Either::Right(resolver.call(dst)),
+            Self::TrustDns(http, _) => Either::Right(http.call(dst)),
         }
     }
 }
 
+/// A resolver recording how long lookups take, in the `ConnectTimings` of
+/// the connection being made, and sorting the addresses by `IpFamily`.
+#[derive(Clone)]
+pub(crate) struct TimedResolver<R>(R, Option<IpFamily>);
+
+impl<R> Service<Name> for TimedResolver<R>
+where
+    R: Service<Name>,
+    R::Response: Iterator<Item = IpAddr>,
+    R::Error: Into<BoxError>,
+    R::Future: Send + 'static,
+{
+    type Response = std::vec::IntoIter<IpAddr>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolving = self.0.call(name);
+        let family = self.1;
+        let timings = ConnectTimings::current();
+        Box::pin(async move {
+            let start = Instant::now();
+            let res = resolving.await;
+            if let Some(timings) = timings {
+                timings.resolved(start);
+            }
+            let addrs = res.map_err(Into::into)?;
+            let addrs = match family {
+                Some(family) => {
+                    let addrs = family.sort(addrs);
+                    if addrs.is_empty() {
+                        match family {
+                            IpFamily::V4Only => return Err("no IPv4 address found".into()),
+                            IpFamily::V6Only => return Err("no IPv6 address found".into()),
+                            _ => (),
+                        }
+                    }
+                    addrs
+                }
+                None => addrs.collect(),
+            };
+            Ok(addrs.into_iter())
+        })
+    }
+}
+
+/// Establishes TCP connections, writing the PROXY protocol header, if any,
+/// before the connection is used for anything else.
+#[derive(Clone)]
+struct TcpConnector {
+    http: HttpConnector,
+    proxy_protocol: Option<ProxyProtocol>,
+    socket: Arc<SocketOptions>,
+}
+
+impl Service<Uri> for TcpConnector {
+    type Response = tokio::net::TcpStream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connecting: Self::Future = if self.socket.applies_before_connect() {
+            Box::pin(socket::connect(self.http.clone(), self.socket.clone(), dst))
+        } else {
+            let connecting = self.http.call(dst);
+            Box::pin(async move { Ok(connecting.await?) })
+        };
+        let proxy_protocol = self.proxy_protocol.clone();
+        Box::pin(async move {
+            use tokio::io::AsyncWriteExt;
+
+            let start = Instant::now();
+            let mut tcp = connecting.await?;
+            ConnectTimings::tcp_connected(start);
+            ConnectTimings::bound(tcp.local_addr().ok());
+            #[cfg(feature = "tracing")]
+            {
+                if let Ok(addr) = tcp.peer_addr() {
+                    trace::record(&trace::Span::current(), "remote_addr", addr);
+                }
+            }
+            if let Some(proxy_protocol) = proxy_protocol {
+                let header = proxy_protocol.encode(tcp.peer_addr()?);
+                tcp.write_all(&header).await?;
+            }
+            Ok(tcp)
+        })
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct Connector {
     inner: Inner,
     proxies: Arc<Vec<Proxy>>,
+    proxy_failover: Option<ProxyHealth>,
+    proxy_protocol: Option<ProxyProtocol>,
     verbose: verbose::Wrapper,
     timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    pool: PoolTracker,
+    warm: Arc<WarmConns>,
+    warm_timeout: Option<Duration>,
+    socket: Arc<SocketOptions>,
     #[cfg(feature = "__tls")]
     nodelay: bool,
     #[cfg(feature = "__tls")]
     user_agent: Option<HeaderValue>,
+    #[cfg(feature = "__tls")]
+    pins: Arc<CertificatePins>,
+    #[cfg(feature = "default-tls")]
+    tls_verifier: Option<Arc<TlsVerifier>>,
+    // Whether native-tls verified the certificates it accepted, for the
+    // `tls_verifier`.
+    #[cfg(feature = "default-tls")]
+    native_certs_verification: bool,
+    // The TLS connectors for `ClientBuilder::host_tls_config`, by host
+    // pattern, and whether they verify certificates.
+    #[cfg(feature = "default-tls")]
+    native_host_tls: Arc<Vec<(String, (TlsConnector, bool))>>,
+    #[cfg(feature = "rustls-tls")]
+    rustls_host_tls: Arc<Vec<(String, Arc<rustls::ClientConfig>)>>,
+    // Overrides the URL host in the handshake with the server.
+    #[cfg(feature = "__tls")]
+    tls_server_name: Option<String>,
+    // Sends the first request as early data, with the configs of
+    // `with_tls_early_data`.
+    #[cfg(feature = "rustls-tls")]
+    tls_early_data: bool,
+    #[cfg(feature = "__tls")]
+    tls_info: bool,
 }
 
+/// The connections opened by `preconnect` waiting for a request, by pool
+/// key. They are closed once the last `Connector` using them is dropped.
+#[derive(Default)]
+struct WarmConns(Mutex<HashMap<String, Vec<Arc<Mutex<Warm>>>>>);
+
+impl WarmConns {
+    fn close(&self) {
+        for (_, conns) in self.0.lock().unwrap().drain() {
+            for warm in conns {
+                let mut warm = warm.lock().unwrap();
+                warm.conn = None;
+                if let Some(watcher) = warm.watcher.take() {
+                    watcher.wake();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for WarmConns {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// A connection opened by `preconnect`, until hyper takes it.
+///
+/// hyper has no way to add a connection to its pool without a request, so
+/// a task watches the connection instead, and closes it when the server
+/// does, or once it was idle for the pool idle timeout.
+struct Warm {
+    conn: Option<Conn>,
+    // What the server sent meanwhile, handed on with the connection.
+    read: bytes::BytesMut,
+    // Wakes the watching task once the connection is taken.
+    watcher: Option<Waker>,
+}
+
+// The most a server may send on a connection nothing was asked on.
+const WARM_READ_MAX: usize = 64 * 1024;
+
 #[derive(Clone)]
 enum Inner {
     #[cfg(not(feature = "__tls"))]
     Http(HttpConnector),
+    // The second connector is for the handshake with HTTPS proxies, without
+    // ALPN, like `tls_proxy`.
     #[cfg(feature = "default-tls")]
-    DefaultTls(HttpConnector, TlsConnector),
+    DefaultTls(HttpConnector, TlsConnector, TlsConnector),
     #[cfg(feature = "rustls-tls")]
     RustlsTls {
         http: HttpConnector,
@@ -147,14 +356,21 @@ impl Connector {
             inner: Inner::Http(http),
             verbose: verbose::OFF,
             proxies,
+            proxy_failover: None,
+            proxy_protocol: None,
             timeout: None,
+            write_timeout: None,
+            pool: PoolTracker::default(),
+            warm: Arc::default(),
+            warm_timeout: None,
+            socket: Arc::default(),
         }
     }
 
     #[cfg(feature = "default-tls")]
     pub(crate) fn new_default_tls<T>(
         http: HttpConnector,
-        tls: TlsConnectorBuilder,
+        mut tls: TlsConnectorBuilder,
         proxies: Arc<Vec<Proxy>>,
         user_agent: Option<HeaderValue>,
         local_addr: T,
@@ -163,10 +379,13 @@ impl Connector {
     where
         T: Into<Option<IpAddr>>,
     {
-        let tls = tls.build().map_err(crate::error::builder)?;
-        Ok(Self::from_built_default_tls(
+        let built = tls.build().map_err(crate::error::builder)?;
+        tls.request_alpns(&[]);
+        let tls_proxy = tls.build().map_err(crate::error::builder)?;
+        Ok(Self::default_tls(
             http,
-            tls,
+            built,
+            tls_proxy,
             proxies,
             user_agent,
             local_addr,
@@ -174,9 +393,9 @@ impl Connector {
         ))
     }
 
-    #[cfg(feature = "default-tls")]
+    #[cfg(feature = "native-tls")]
     pub(crate) fn from_built_default_tls<T> (
-        mut http: HttpConnector,
+        http: HttpConnector,
         tls: TlsConnector,
         proxies: Arc<Vec<Proxy>>,
         user_agent: Option<HeaderValue>,
@@ -184,17 +403,53 @@ impl Connector {
         nodelay: bool) -> Connector
         where
             T: Into<Option<IpAddr>>,
+    {
+        Self::default_tls(http, tls.clone(), tls, proxies, user_agent, local_addr, nodelay)
+    }
+
+    #[cfg(feature = "default-tls")]
+    fn default_tls<T>(
+        mut http: HttpConnector,
+        tls: TlsConnector,
+        tls_proxy: TlsConnector,
+        proxies: Arc<Vec<Proxy>>,
+        user_agent: Option<HeaderValue>,
+        local_addr: T,
+        nodelay: bool,
+    ) -> Connector
+    where
+        T: Into<Option<IpAddr>>,
     {
         http.set_local_address(local_addr.into());
         http.enforce_http(false);
 
         Connector {
-            inner: Inner::DefaultTls(http, tls),
+            inner: Inner::DefaultTls(http, tls, tls_proxy),
             proxies,
+            proxy_failover: None,
+            proxy_protocol: None,
             verbose: verbose::OFF,
             timeout: None,
+            write_timeout: None,
+            pool: PoolTracker::default(),
+            warm: Arc::default(),
+            warm_timeout: None,
+            socket: Arc::default(),
             nodelay,
             user_agent,
+            pins: Arc::default(),
+            #[cfg(feature = "default-tls")]
+            tls_verifier: None,
+            #[cfg(feature = "default-tls")]
+            native_certs_verification: true,
+            #[cfg(feature = "default-tls")]
+            native_host_tls: Arc::default(),
+            #[cfg(feature = "rustls-tls")]
+            rustls_host_tls: Arc::default(),
+            tls_server_name: None,
+            #[cfg(feature = "rustls-tls")]
+            tls_early_data: false,
+            tls_info: false,
         }
     }
 
@@ -229,21 +484,349 @@ impl Connector {
                 tls_proxy,
             },
             proxies,
+            proxy_failover: None,
+            proxy_protocol: None,
             verbose: verbose::OFF,
             timeout: None,
+            write_timeout: None,
+            pool: PoolTracker::default(),
+            warm: Arc::default(),
+            warm_timeout: None,
+            socket: Arc::default(),
             nodelay,
             user_agent,
+            pins: Arc::default(),
+            #[cfg(feature = "default-tls")]
+            tls_verifier: None,
+            #[cfg(feature = "default-tls")]
+            native_certs_verification: true,
+            #[cfg(feature = "default-tls")]
+            native_host_tls: Arc::default(),
+            #[cfg(feature = "rustls-tls")]
+            rustls_host_tls: Arc::default(),
+            tls_server_name: None,
+            tls_early_data: false,
+            tls_info: false,
+        }
+    }
+
+    /// Returns a copy of this connector that uses a different list of proxies.
+    pub(crate) fn with_proxies(&self, proxies: Arc<Vec<Proxy>>) -> Connector {
+        let mut connector = self.clone();
+
+        #[cfg(feature = "rustls-tls")]
+        match connector.inner {
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(..) => (),
+            Inner::RustlsTls { ref tls, ref mut tls_proxy, .. } => {
+                *tls_proxy = if proxies.is_empty() {
+                    tls.clone()
+                } else {
+                    let mut cfg = (**tls).clone();
+                    cfg.alpn_protocols.clear();
+                    Arc::new(cfg)
+                };
+            }
+        }
+
+        connector.proxies = proxies;
+        connector.warm = Arc::default();
+        connector
+    }
+
+    /// Returns a copy of this connector that uses `name` instead of the URL
+    /// host in the TLS handshake with the server.
+    #[cfg(feature = "__tls")]
+    pub(crate) fn with_tls_server_name(&self, name: String) -> Connector {
+        let mut connector = self.clone();
+        connector.tls_server_name = Some(name);
+        connector.warm = Arc::default();
+        connector
+    }
+
+    /// Returns a copy of this connector that sends the first request on a
+    /// connection as TLS early data, when the session is resumed.
+    #[cfg(feature = "rustls-tls")]
+    pub(crate) fn with_tls_early_data(&self) -> Connector {
+        let mut connector = self.clone();
+
+        match connector.inner {
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(..) => (),
+            Inner::RustlsTls { ref mut tls, .. } => *tls = Arc::new(early_data_config(tls)),
         }
+        let host_tls = self
+            .rustls_host_tls
+            .iter()
+            .map(|(pattern, tls)| (pattern.clone(), Arc::new(early_data_config(tls))))
+            .collect();
+
+        connector.rustls_host_tls = Arc::new(host_tls);
+        connector.tls_early_data = true;
+        connector.warm = Arc::default();
+        connector
     }
 
     pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
         self.timeout = timeout;
     }
 
+    pub(crate) fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout;
+    }
+
     pub(crate) fn set_verbose(&mut self, enabled: bool) {
         self.verbose.0 = enabled;
     }
 
+    pub(crate) fn set_proxy_failover(&mut self, cooldown: Option<Duration>) {
+        self.proxy_failover = cooldown.map(ProxyHealth::new);
+    }
+
+    pub(crate) fn set_proxy_protocol(&mut self, proxy_protocol: Option<ProxyProtocol>) {
+        self.proxy_protocol = proxy_protocol;
+    }
+
+    pub(crate) fn set_pool(&mut self, pool: PoolTracker) {
+        self.pool = pool;
+    }
+
+    pub(crate) fn set_socket_options(&mut self, socket: SocketOptions) {
+        self.socket = Arc::new(socket);
+    }
+
+    pub(crate) fn set_pool_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.warm_timeout = timeout;
+    }
+
+    #[cfg(feature = "__tls")]
+    pub(crate) fn set_tls_info(&mut self, enabled: bool) {
+        self.tls_info = enabled;
+    }
+
+    #[cfg(feature = "__tls")]
+    pub(crate) fn set_certificate_pins(&mut self, pins: CertificatePins) {
+        self.pins = Arc::new(pins);
+    }
+
+    #[cfg(feature = "default-tls")]
+    pub(crate) fn set_native_host_tls(&mut self, host_tls: Vec<(String, (TlsConnector, bool))>) {
+        self.native_host_tls = Arc::new(host_tls);
+    }
+
+    #[cfg(feature = "rustls-tls")]
+    pub(crate) fn set_rustls_host_tls(
+        &mut self,
+        host_tls: Vec<(String, Arc<rustls::ClientConfig>)>,
+    ) {
+        self.rustls_host_tls = Arc::new(host_tls);
+    }
+
+    #[cfg(feature = "default-tls")]
+    pub(crate) fn set_tls_verifier(
+        &mut self,
+        verifier: Option<Arc<TlsVerifier>>,
+        certs_verification: bool,
+    ) {
+        self.tls_verifier = verifier;
+        self.native_certs_verification = certs_verification;
+    }
+
+    /// The name to send in SNI, and to verify the certificate of the server
+    /// `host` against.
+    #[cfg(feature = "__tls")]
+    fn tls_server_name<'a>(&'a self, host: &'a str) -> &'a str {
+        self.tls_server_name.as_ref().map(|name| &name[..]).unwrap_or(host)
+    }
+
+    /// Checks the pins and runs the `tls_verifier` callback, which native-tls
+    /// can only do after the handshake. The callback gets `server_name`, as
+    /// with rustls.
+    #[cfg(feature = "default-tls")]
+    fn verify_native_tls_peer<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        host: &str,
+        server_name: &str,
+        io: &native_tls_conn::TlsStream<S>,
+    ) -> Result<(), BoxError> {
+        if !self.pins.matches(host) && self.tls_verifier.is_none() {
+            return Ok(());
+        }
+        let cert = match io.get_ref().peer_certificate()? {
+            Some(cert) => Some(cert.to_der()?),
+            None => None,
+        };
+        // native-tls only gives the server's own certificate.
+        let chain = cert.as_ref().map(|der| &der[..]).into_iter().collect::<Vec<_>>();
+        self.pins.verify(host, &chain)?;
+
+        if let Some(ref verifier) = self.tls_verifier {
+            let verified = host_tls(&self.native_host_tls, host)
+                .map(|&(_, verified)| verified)
+                .unwrap_or(self.native_certs_verification);
+            let disabled = crate::tls::VerificationDisabled;
+            let verify_error = if verified { None } else { Some(&disabled as _) };
+            verifier(&ServerCertInfo::new(server_name, &chain, verify_error))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "rustls-tls")]
+    fn verify_rustls_pins<S>(
+        &self,
+        host: &str,
+        io: &tokio_rustls::client::TlsStream<S>,
+    ) -> Result<(), BoxError> {
+        use rustls::Session;
+
+        if !self.pins.matches(host) {
+            return Ok(());
+        }
+        let (_, session) = io.get_ref();
+        let certs = session.get_peer_certificates().unwrap_or_default();
+        let chain = certs.iter().map(|cert| &cert.0[..]).collect::<Vec<_>>();
+        self.pins.verify(host, &chain)?;
+        Ok(())
+    }
+
+    /// Whether to send early data to `host`, which skips waiting for the
+    /// handshake that the pins and `TlsInfo` need.
+    #[cfg(feature = "rustls-tls")]
+    fn tls_early_data(&self, host: &str) -> bool {
+        self.tls_early_data && !self.tls_info && !self.pins.matches(host)
+    }
+
+    /// Opens a connection to `dst` without sending a request, and keeps it
+    /// for the next connection hyper asks for to `dst`.
+    pub(crate) fn preconnect(&self, dst: Uri) -> impl Future<Output = Result<(), BoxError>> {
+        let connecting = self.connect(dst.clone());
+        let connector = self.clone();
+        async move {
+            let conn = connecting.await?;
+            if let Some(ref timings) = conn.timings {
+                // The connection is idle in the pool for its first request.
+                timings.pooled();
+            }
+            if let Some(ref open) = conn.open {
+                open.idle();
+            }
+            let warm = Arc::new(Mutex::new(Warm {
+                conn: Some(conn),
+                read: bytes::BytesMut::new(),
+                watcher: None,
+            }));
+            connector
+                .warm
+                .0
+                .lock()
+                .unwrap()
+                .entry(pool::key(&dst))
+                .or_default()
+                .push(warm.clone());
+            tokio::spawn(watch_warm(
+                warm,
+                Arc::downgrade(&connector.warm),
+                pool::key(&dst),
+                connector.warm_timeout,
+            ));
+            Ok(())
+        }
+    }
+
+    fn take_warm(&self, dst: &Uri) -> Option<Conn> {
+        let key = pool::key(dst);
+        let mut warm = self.warm.0.lock().unwrap();
+        let conns = warm.get_mut(&key)?;
+        let mut taken = None;
+        while let Some(entry) = conns.pop() {
+            let mut next = entry.lock().unwrap();
+            // Check the connection is still alive, in case the watching task
+            // hasn't seen it closed yet.
+            let cx = &mut Context::from_waker(futures_util::task::noop_waker_ref());
+            if let Poll::Ready(()) = next.poll_closed(cx) {
+                continue;
+            }
+            if let Some(watcher) = next.watcher.take() {
+                watcher.wake();
+            }
+            taken = next.take();
+            if taken.is_some() {
+                break;
+            }
+        }
+        if conns.is_empty() {
+            warm.remove(&key);
+        }
+        taken
+    }
+
+    /// Closes the connections opened by `preconnect`.
+    pub(crate) fn close_warm(&self) {
+        self.warm.close();
+    }
+
+    fn connect(&self, dst: Uri) -> Connecting {
+        log::debug!("starting new connection: {:?}", dst);
+        let span = span!("connect", uri = %dst, remote_addr = tracing::field::Empty);
+        let timeout = self.timeout;
+        let opening = self.pool.open(&dst);
+        let connector = self.clone();
+        let connecting: Connecting = Box::pin(async move {
+            #[cfg(feature = "pac")]
+            {
+                if let Some(resolving) = crate::proxy::resolve_pac(&connector.proxies, &dst) {
+                    resolving.await;
+                }
+            }
+            let routes = connector.routes(&connector.proxies, &dst);
+            if !routes.is_empty() {
+                connector.connect_via_routes(dst, routes).await
+            } else {
+                with_timeout(connector.connect_with_maybe_proxy(dst, false), timeout).await
+            }
+        });
+
+        let write_timeout = self.write_timeout;
+        let timings = ConnectTimings::new();
+        let connecting = CONNECT_TIMINGS.scope(timings.clone(), trace::instrument(connecting, span));
+        Box::pin(async move {
+            // Wait for room under the connection limits before connecting.
+            let open = opening.await?;
+            let mut conn = connecting.await?;
+            timings.ready();
+            conn.timings = Some(timings);
+            conn.open = Some(open);
+            if write_timeout.is_some() {
+                conn.inner = Box::new(TimeoutConn {
+                    inner: conn.inner,
+                    write_timeout: IdleTimeout::new(write_timeout),
+                });
+            }
+            Ok(conn)
+        })
+    }
+
+    fn tcp(&self, http: HttpConnector) -> TcpConnector {
+        TcpConnector {
+            http,
+            proxy_protocol: self.proxy_protocol.clone(),
+            socket: self.socket.clone(),
+        }
+    }
+
+    /// The connector for the TCP connections to the servers and proxies.
+    #[cfg(feature = "socks")]
+    fn http(&self) -> &HttpConnector {
+        match self.inner {
+            #[cfg(not(feature = "__tls"))]
+            Inner::Http(ref http) => http,
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(ref http, _, _) => http,
+            #[cfg(feature = "rustls-tls")]
+            Inner::RustlsTls { ref http, .. } => http,
+        }
+    }
+
     #[cfg(feature = "socks")]
     async fn connect_socks(
         &self,
@@ -264,20 +847,30 @@ impl Connector {
 
         match &self.inner {
             #[cfg(feature = "default-tls")]
-            Inner::DefaultTls(_http, tls) => {
+            Inner::DefaultTls(_http, tls, _) => {
                 if dst.scheme() == Some(&Scheme::HTTPS) {
                     let host = dst
                         .host()
                         .ok_or("no host in url")?
                         .to_string();
-                    let conn = socks::connect(proxy, dst, dns).await?;
-                    let tls_connector = tokio_tls::TlsConnector::from(tls.clone());
-                    let io = tls_connector
-                        .connect(&host, conn)
-                        .await?;
+                    let tls = host_tls(&self.native_host_tls, &host).map(|(tls, _)| tls).unwrap_or(tls);
+                    let server_name = self.tls_server_name(&host);
+                    let start = Instant::now();
+                    let conn = socks::connect(proxy, dst, dns, self.http().clone(), self.socket.clone()).await?;
+                    ConnectTimings::add_connect(start.elapsed());
+                    let start = Instant::now();
+                    let io = trace::instrument(
+                        native_tls_conn::connect(tls, server_name, conn),
+                        span!("tls_handshake", server_name),
+                    ).await?;
+                    ConnectTimings::add_tls(start.elapsed());
+                    self.verify_native_tls_peer(&host, server_name, &io)?;
                     return Ok(Conn {
-                        inner: self.verbose.wrap(NativeTlsConn { inner: io }),
+                        inner: self.verbose.wrap(NativeTlsConn { inner: io, tls_info: self.tls_info }),
                         is_proxy: false,
+                        proxy: None,
+                        timings: None,
+                        open: None,
                     });
                 }
             }
@@ -287,21 +880,39 @@ impl Connector {
                     use tokio_rustls::webpki::DNSNameRef;
                     use tokio_rustls::TlsConnector as RustlsConnector;
 
-                    let tls = tls_proxy.clone();
                     let host = dst
                         .host()
                         .ok_or("no host in url")?
                         .to_string();
-                    let conn = socks::connect(proxy, dst, dns).await?;
-                    let dnsname = DNSNameRef::try_from_ascii_str(&host)
+                    // Without ALPN, like `tls_proxy`.
+                    let tls = match host_tls(&self.rustls_host_tls, &host) {
+                        Some(tls) => {
+                            let mut tls = (**tls).clone();
+                            tls.alpn_protocols.clear();
+                            Arc::new(tls)
+                        }
+                        None => tls_proxy.clone(),
+                    };
+                    let start = Instant::now();
+                    let conn = socks::connect(proxy, dst, dns, self.http().clone(), self.socket.clone()).await?;
+                    ConnectTimings::add_connect(start.elapsed());
+                    let server_name = self.tls_server_name(&host);
+                    let dnsname = DNSNameRef::try_from_ascii_str(server_name)
                         .map(|dnsname| dnsname.to_owned())
                         .map_err(|_| "Invalid DNS Name")?;
-                    let io = RustlsConnector::from(tls)
-                        .connect(dnsname.as_ref(), conn)
-                        .await?;
+                    let start = Instant::now();
+                    let io = trace::instrument(
+                        RustlsConnector::from(tls).connect(dnsname.as_ref(), conn),
+                        span!("tls_handshake", server_name),
+                    ).await?;
+                    ConnectTimings::add_tls(start.elapsed());
+                    self.verify_rustls_pins(&host, &io)?;
                     return Ok(Conn {
-                        inner: self.verbose.wrap(RustlsTlsConn { inner: io }),
+                        inner: self.verbose.wrap(RustlsTlsConn { inner: io, tls_info: self.tls_info }),
                         is_proxy: false,
+                        proxy: None,
+                        timings: None,
+                        open: None,
                     });
                 }
             }
@@ -309,9 +920,15 @@ impl Connector {
             Inner::Http(_) => ()
         }
 
-        socks::connect(proxy, dst, dns).await.map(|tcp| Conn {
+        let start = Instant::now();
+        let tcp = socks::connect(proxy, dst, dns, self.http().clone(), self.socket.clone()).await?;
+        ConnectTimings::add_connect(start.elapsed());
+        Ok(Conn {
             inner: self.verbose.wrap(tcp),
             is_proxy: false,
+            proxy: None,
+            timings: None,
+            open: None,
         })
     }
 
@@ -320,71 +937,188 @@ impl Connector {
         dst: Uri,
         is_proxy: bool,
     ) -> Result<Conn, BoxError> {
-        match self.inner {
+        match &self.inner {
             #[cfg(not(feature = "__tls"))]
-            Inner::Http(mut http) => {
-                let io = http.call(dst).await?;
+            Inner::Http(http) => {
+                let io = self.tcp(http.clone()).call(dst).await?;
                 Ok(Conn {
                     inner: self.verbose.wrap(io),
                     is_proxy,
+                    proxy: None,
+                    timings: None,
+                    open: None,
                 })
             }
             #[cfg(feature = "default-tls")]
-            Inner::DefaultTls(http, tls) => {
+            Inner::DefaultTls(http, tls, _) => {
                 let mut http = http.clone();
-
+                let https = dst.scheme() == Some(&Scheme::HTTPS);
 
                 // Disable Nagle's algorithm for TLS handshake
                 //
                 // https://www.openssl.org/docs/man1.1.1/man3/SSL_connect.html#NOTES
-                if !self.nodelay && (dst.scheme() == Some(&Scheme::HTTPS)) {
+                if !self.nodelay && https {
                     http.set_nodelay(true);
                 }
 
-                let tls_connector = tokio_tls::TlsConnector::from(tls.clone());
-                let mut http = hyper_tls::HttpsConnector::from((http, tls_connector));
-                let io = http.call(dst).await?;
+                let host = tls_host(&dst);
+                let tcp = self.tcp(http).call(dst).await?;
+                if !https {
+                    return Ok(Conn {
+                        inner: self.verbose.wrap(tcp),
+                        is_proxy,
+                        proxy: None,
+                        timings: None,
+                        open: None,
+                    });
+                }
 
-                if let hyper_tls::MaybeHttpsStream::Https(stream) = &io {
-                    if !self.nodelay {
-                        stream.get_ref().set_nodelay(false)?;
-                    }
+                let tls = host_tls(&self.native_host_tls, &host).map(|(tls, _)| tls).unwrap_or(tls);
+                // The server name override isn't for the proxy.
+                let server_name = if is_proxy { &host } else { self.tls_server_name(&host) };
+                let start = Instant::now();
+                let io = trace::instrument(
+                    native_tls_conn::connect(tls, server_name, tcp),
+                    span!("tls_handshake", server_name),
+                ).await?;
+                ConnectTimings::add_tls(start.elapsed());
+                self.verify_native_tls_peer(&host, server_name, &io)?;
+
+                if !self.nodelay {
+                    io.get_io().set_nodelay(false)?;
                 }
 
                 Ok(Conn {
-                    inner: self.verbose.wrap(io),
+                    inner: self.verbose.wrap(NativeTlsConn { inner: io, tls_info: self.tls_info }),
                     is_proxy,
+                    proxy: None,
+                    timings: None,
+                    open: None,
                 })
             }
             #[cfg(feature = "rustls-tls")]
             Inner::RustlsTls { http, tls, .. } => {
+                use tokio_rustls::webpki::DNSNameRef;
+                use tokio_rustls::TlsConnector as RustlsConnector;
+
                 let mut http = http.clone();
+                let https = dst.scheme() == Some(&Scheme::HTTPS);
 
                 // Disable Nagle's algorithm for TLS handshake
                 //
                 // https://www.openssl.org/docs/man1.1.1/man3/SSL_connect.html#NOTES
-                if !self.nodelay && (dst.scheme() == Some(&Scheme::HTTPS)) {
+                if !self.nodelay && https {
                     http.set_nodelay(true);
                 }
 
-                let mut http = hyper_rustls::HttpsConnector::from((http, tls.clone()));
-                let io = http.call(dst).await?;
+                let host = tls_host(&dst);
+                let tcp = self.tcp(http).call(dst).await?;
+                if !https {
+                    return Ok(Conn {
+                        inner: self.verbose.wrap(tcp),
+                        is_proxy,
+                        proxy: None,
+                        timings: None,
+                        open: None,
+                    });
+                }
 
-                if let hyper_rustls::MaybeHttpsStream::Https(stream) = &io {
-                    if !self.nodelay {
-                        let (io, _) = stream.get_ref();
-                        io.set_nodelay(false)?;
-                    }
+                // The server name override isn't for the proxy.
+                let server_name = if is_proxy { &host } else { self.tls_server_name(&host) };
+                let dnsname = DNSNameRef::try_from_ascii_str(server_name)
+                    .map_err(|_| "Invalid DNS Name")?;
+                let tls = host_tls(&self.rustls_host_tls, &host).unwrap_or(tls);
+                let early_data = !is_proxy && self.tls_early_data(&host);
+                let start = Instant::now();
+                let io = trace::instrument(
+                    RustlsConnector::from(tls.clone())
+                        .early_data(early_data)
+                        .connect(dnsname, tcp),
+                    span!("tls_handshake", server_name),
+                ).await?;
+                ConnectTimings::add_tls(start.elapsed());
+                self.verify_rustls_pins(&host, &io)?;
+
+                if !self.nodelay {
+                    let (io, _) = io.get_ref();
+                    io.set_nodelay(false)?;
                 }
 
                 Ok(Conn {
-                    inner: self.verbose.wrap(io),
+                    inner: self.verbose.wrap(RustlsTlsConn { inner: io, tls_info: self.tls_info }),
                     is_proxy,
+                    proxy: None,
+                    timings: None,
+                    open: None,
                 })
             }
         }
     }
 
+    /// The routes `proxies` intercept `dst` with, in the order to try them.
+    ///
+    /// A route is either a proxy, or `None` for a direct connection. This
+    /// is empty when no proxy intercepts `dst`.
+    pub(crate) fn routes(&self, proxies: &[Proxy], dst: &Uri) -> Vec<Option<ProxyScheme>> {
+        let mut routes = Vec::new();
+        for prox in proxies {
+            routes.extend(prox.intercept_routes(dst));
+            // Without failover, only the first intercepting proxy is used.
+            if !routes.is_empty() && self.proxy_failover.is_none() {
+                break;
+            }
+        }
+        if let Some(ref health) = self.proxy_failover {
+            health.sort(&mut routes);
+        }
+        routes
+    }
+
+    /// Connects through the first route that works.
+    async fn connect_via_routes(
+        self,
+        dst: Uri,
+        routes: Vec<Option<ProxyScheme>>,
+    ) -> Result<Conn, BoxError> {
+        let mut last_err = None;
+        for route in routes {
+            let proxy_scheme = match route {
+                Some(proxy_scheme) => proxy_scheme,
+                None => {
+                    let connecting = self.clone().connect_with_maybe_proxy(dst.clone(), false);
+                    match with_timeout(connecting, self.timeout).await {
+                        Ok(conn) => return Ok(conn),
+                        Err(err) => {
+                            log::debug!("direct connection failed, trying the next route: {}", err);
+                            last_err = Some(err);
+                            continue;
+                        }
+                    }
+                }
+            };
+            let proxy_url = proxy_scheme.to_url();
+            let connecting = self.clone().connect_via_proxy(dst.clone(), proxy_scheme.clone());
+            match with_timeout(connecting, self.timeout).await {
+                Ok(mut conn) => {
+                    if let Some(ref health) = self.proxy_failover {
+                        health.mark_healthy(&proxy_scheme);
+                    }
+                    conn.proxy = Some(ProxyUrl(proxy_url));
+                    return Ok(conn);
+                }
+                Err(err) => {
+                    if let Some(ref health) = self.proxy_failover {
+                        log::debug!("proxy({:?}) failed, trying the next one: {}", proxy_scheme, err);
+                        health.mark_unhealthy(&proxy_scheme);
+                    }
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        Err(last_err.expect("at least one route"))
+    }
+
     async fn connect_via_proxy(
         self,
         dst: Uri,
@@ -405,31 +1139,37 @@ impl Connector {
 
         match &self.inner {
             #[cfg(feature = "default-tls")]
-            Inner::DefaultTls(http, tls) => {
+            Inner::DefaultTls(http, tls, tls_proxy) => {
                 if dst.scheme() == Some(&Scheme::HTTPS) {
                     let host = dst.host().to_owned();
                     let port = dst.port().map(|p| p.as_u16()).unwrap_or(443);
-                    let http = http.clone();
-                    let tls_connector = tokio_tls::TlsConnector::from(tls.clone());
+                    let http = self.tcp(http.clone());
+                    let tls_connector = tokio_tls::TlsConnector::from(tls_proxy.clone());
                     let mut http = hyper_tls::HttpsConnector::from((http, tls_connector));
                     let conn = http.call(proxy_dst).await?;
                     log::trace!("tunneling HTTPS over proxy");
-                    let tunneled = tunnel(
-                        conn,
-                        host
-                            .ok_or("no host in url")?
-                            .to_string(),
-                        port,
-                        self.user_agent.clone(),
-                        auth
+                    let host = host.ok_or("no host in url")?;
+                    let start = Instant::now();
+                    let tunneled = trace::instrument(
+                        tunnel(conn, host.to_string(), port, self.user_agent.clone(), auth),
+                        span!("tunnel", host, port),
+                    ).await?;
+                    ConnectTimings::add_connect(start.elapsed());
+                    let tls = host_tls(&self.native_host_tls, host).map(|(tls, _)| tls).unwrap_or(tls);
+                    let server_name = self.tls_server_name(host);
+                    let start = Instant::now();
+                    let io = trace::instrument(
+                        native_tls_conn::connect(tls, server_name, tunneled),
+                        span!("tls_handshake", server_name),
                     ).await?;
-                    let tls_connector = tokio_tls::TlsConnector::from(tls.clone());
-                    let io = tls_connector
-                        .connect(&host.ok_or("no host in url")?, tunneled)
-                        .await?;
+                    ConnectTimings::add_tls(start.elapsed());
+                    self.verify_native_tls_peer(host, server_name, &io)?;
                     return Ok(Conn {
-                        inner: self.verbose.wrap(NativeTlsConn { inner: io }),
+                        inner: self.verbose.wrap(NativeTlsConn { inner: io, tls_info: self.tls_info }),
                         is_proxy: false,
+                        proxy: None,
+                        timings: None,
+                        open: None,
                     });
                 }
             }
@@ -448,23 +1188,38 @@ impl Connector {
                         .ok_or("no host in url")?
                         .to_string();
                     let port = dst.port().map(|r| r.as_u16()).unwrap_or(443);
-                    let http = http.clone();
+                    let http = self.tcp(http.clone());
                     let mut http = hyper_rustls::HttpsConnector::from((http, tls_proxy.clone()));
-                    let tls = tls.clone();
+                    let tls = host_tls(&self.rustls_host_tls, &host).unwrap_or(tls).clone();
                     let conn = http.call(proxy_dst).await?;
                     log::trace!("tunneling HTTPS over proxy");
-                    let maybe_dnsname = DNSNameRef::try_from_ascii_str(&host)
+                    let server_name = self.tls_server_name(&host);
+                    let maybe_dnsname = DNSNameRef::try_from_ascii_str(server_name)
                         .map(|dnsname| dnsname.to_owned())
                         .map_err(|_| "Invalid DNS Name");
-                    let tunneled = tunnel(conn, host, port, self.user_agent.clone(), auth).await?;
+                    let start = Instant::now();
+                    let tunneled = trace::instrument(
+                        tunnel(conn, host.clone(), port, self.user_agent.clone(), auth),
+                        span!("tunnel", host = %host, port),
+                    ).await?;
+                    ConnectTimings::add_connect(start.elapsed());
                     let dnsname = maybe_dnsname?;
-                    let io = RustlsConnector::from(tls)
-                        .connect(dnsname.as_ref(), tunneled)
-                        .await?;
+                    let start = Instant::now();
+                    let io = trace::instrument(
+                        RustlsConnector::from(tls)
+                            .early_data(self.tls_early_data(&host))
+                            .connect(dnsname.as_ref(), tunneled),
+                        span!("tls_handshake", server_name),
+                    ).await?;
+                    ConnectTimings::add_tls(start.elapsed());
+                    self.verify_rustls_pins(&host, &io)?;
 
                     return Ok(Conn {
-                        inner: self.verbose.wrap(RustlsTlsConn { inner: io }),
+                        inner: self.verbose.wrap(RustlsTlsConn { inner: io, tls_info: self.tls_info }),
                         is_proxy: false,
+                        proxy: None,
+                        timings: None,
+                        open: None,
                     });
                 }
             }
@@ -476,6 +1231,38 @@ impl Connector {
     }
 }
 
+/// Returns a copy of `tls` that sends early data.
+///
+/// hyper picks the HTTP version before such a handshake completes, so h2 is
+/// only offered when it's the only protocol.
+#[cfg(feature = "rustls-tls")]
+fn early_data_config(tls: &rustls::ClientConfig) -> rustls::ClientConfig {
+    let mut tls = tls.clone();
+    tls.enable_early_data = true;
+    if tls.alpn_protocols.len() > 1 {
+        tls.alpn_protocols.retain(|alpn| alpn != b"h2");
+    }
+    tls
+}
+
+/// Returns the TLS connector of the first host pattern matching `host`.
+#[cfg(feature = "__tls")]
+fn host_tls<'a, T>(host_tls: &'a [(String, T)], host: &str) -> Option<&'a T> {
+    host_tls
+        .iter()
+        .find(|(pattern, _)| host_matches(pattern, host))
+        .map(|(_, tls)| tls)
+}
+
+/// The host name to verify the certificate of `dst` against.
+#[cfg(feature = "__tls")]
+fn tls_host(dst: &Uri) -> String {
+    dst.host()
+        .unwrap_or("")
+        .trim_matches(|c| c == '[' || c == ']')
+        .to_owned()
+}
+
 fn into_uri(scheme: Scheme, host: Authority) -> Uri {
     // TODO: Should the `http` crate get `From<(Scheme, Authority)> for Uri`?
     http::Uri::builder()
@@ -501,6 +1288,127 @@ where
     }
 }
 
+impl Warm {
+    /// Reads what the server sends, until the connection is closed or taken.
+    fn poll_closed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut buf = [0; 1024];
+        loop {
+            let conn = match self.conn {
+                Some(ref mut conn) => conn,
+                None => return Poll::Ready(()),
+            };
+            match Pin::new(conn).poll_read(cx, &mut buf) {
+                Poll::Ready(Ok(0)) | Poll::Ready(Err(_)) => self.conn = None,
+                Poll::Ready(Ok(n)) if self.read.len() + n > WARM_READ_MAX => self.conn = None,
+                Poll::Ready(Ok(n)) => self.read.extend_from_slice(&buf[..n]),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Takes the connection, with what the server sent meanwhile.
+    fn take(&mut self) -> Option<Conn> {
+        let mut conn = self.conn.take()?;
+        if !self.read.is_empty() {
+            conn.inner = Box::new(Rewind {
+                read: self.read.split().freeze(),
+                inner: conn.inner,
+            });
+        }
+        Some(conn)
+    }
+}
+
+/// Watches a connection opened by `preconnect` until it is taken, closed by
+/// the server, or idle for `timeout`.
+async fn watch_warm(
+    warm: Arc<Mutex<Warm>>,
+    conns: std::sync::Weak<WarmConns>,
+    key: String,
+    timeout: Option<Duration>,
+) {
+    let mut expired = timeout.map(tokio::time::delay_for);
+    futures_util::future::poll_fn(|cx| {
+        if let Some(ref mut expired) = expired {
+            if let Poll::Ready(()) = Pin::new(expired).poll(cx) {
+                return Poll::Ready(());
+            }
+        }
+        let mut warm = warm.lock().unwrap();
+        warm.watcher = Some(cx.waker().clone());
+        warm.poll_closed(cx)
+    })
+    .await;
+
+    // Close the connection, unless it was taken, and forget it.
+    warm.lock().unwrap().conn = None;
+    if let Some(conns) = conns.upgrade() {
+        let mut conns = conns.0.lock().unwrap();
+        if let Some(list) = conns.get_mut(&key) {
+            list.retain(|other| !Arc::ptr_eq(other, &warm));
+            if list.is_empty() {
+                conns.remove(&key);
+            }
+        }
+    }
+}
+
+/// A connection that first yields the bytes already read from it.
+struct Rewind {
+    read: bytes::Bytes,
+    inner: BoxConn,
+}
+
+impl Connection for Rewind {
+    fn connected(&self) -> Connected {
+        self.inner.connected()
+    }
+}
+
+impl AsyncRead for Rewind {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8]
+    ) -> Poll<io::Result<usize>> {
+        if !self.read.is_empty() {
+            let n = std::cmp::min(buf.len(), self.read.len());
+            buf[..n].copy_from_slice(&self.read[..n]);
+            self.read.advance(n);
+            return Poll::Ready(Ok(n));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+
+    unsafe fn prepare_uninitialized_buffer(
+        &self,
+        buf: &mut [MaybeUninit<u8>]
+    ) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl AsyncWrite for Rewind {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8]
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
 impl Service<Uri> for Connector {
     type Response = Conn;
     type Error = BoxError;
@@ -511,21 +1419,11 @@ impl Service<Uri> for Connector {
     }
 
     fn call(&mut self, dst: Uri) -> Self::Future {
-        log::debug!("starting new connection: {:?}", dst);
-        let timeout = self.timeout;
-        for prox in self.proxies.iter() {
-            if let Some(proxy_scheme) = prox.intercept(&dst) {
-                return Box::pin(with_timeout(
-                    self.clone().connect_via_proxy(dst, proxy_scheme),
-                    timeout,
-                ));
-            }
+        if let Some(conn) = self.take_warm(&dst) {
+            log::debug!("using preconnected connection: {:?}", dst);
+            return Box::pin(futures_util::future::ok(conn));
         }
-
-        Box::pin(with_timeout(
-            self.clone().connect_with_maybe_proxy(dst, false),
-            timeout,
-        ))
+        self.connect(dst)
     }
 }
 
@@ -544,15 +1442,246 @@ pin_project! {
         #[pin]
         inner: BoxConn,
         is_proxy: bool,
+        proxy: Option<ProxyUrl>,
+        timings: Option<ConnectTimings>,
+        // Counts the connection as open in the pool stats until dropped.
+        open: Option<OpenConn>,
     }
 }
 
 impl Connection for Conn {
     fn connected(&self) -> Connected {
-        self.inner.connected().proxy(self.is_proxy)
+        let mut connected = self.inner.connected().proxy(self.is_proxy);
+        if let Some(ref proxy) = self.proxy {
+            connected = connected.extra(proxy.clone());
+        }
+        if let Some(ref timings) = self.timings {
+            connected = connected.extra(timings.clone());
+        }
+        if let Some(ref open) = self.open {
+            connected = connected.extra(open.handle());
+        }
+        connected
+    }
+}
+
+tokio::task_local! {
+    /// The timings of the connection being made by the current task.
+    static CONNECT_TIMINGS: ConnectTimings;
+}
+
+/// When the phases of a connection happened, shared with every response
+/// received on it.
+#[derive(Clone)]
+pub(crate) struct ConnectTimings(Arc<Mutex<ConnectPhases>>);
+
+#[derive(Default)]
+struct ConnectPhases {
+    dns: Option<Duration>,
+    connect: Option<Duration>,
+    tls: Option<Duration>,
+    resolved: Option<Instant>,
+    ready: Option<Instant>,
+    last_write: Option<Instant>,
+    reported: bool,
+    local_addr: Option<SocketAddr>,
+}
+
+impl ConnectTimings {
+    fn new() -> ConnectTimings {
+        ConnectTimings(Arc::new(Mutex::new(ConnectPhases::default())))
+    }
+
+    /// The timings of the connection being made by the current task, if any.
+    fn current() -> Option<ConnectTimings> {
+        CONNECT_TIMINGS.try_with(|timings| timings.clone()).ok()
+    }
+
+    fn with_current<F: FnOnce(&mut ConnectPhases)>(f: F) {
+        if let Some(timings) = ConnectTimings::current() {
+            f(&mut timings.0.lock().unwrap());
+        }
+    }
+
+    /// Records the local address of the TCP connection being made.
+    fn bound(local_addr: Option<SocketAddr>) {
+        ConnectTimings::with_current(|phases| phases.local_addr = local_addr);
+    }
+
+    pub(crate) fn local_addr(&self) -> Option<SocketAddr> {
+        self.0.lock().unwrap().local_addr
+    }
+
+    fn resolved(&self, start: Instant) {
+        let mut phases = self.0.lock().unwrap();
+        phases.dns = Some(phases.dns.unwrap_or_default() + start.elapsed());
+        phases.resolved = Some(Instant::now());
+    }
+
+    /// Records the TCP connection being established, after a lookup if one
+    /// was needed.
+    fn tcp_connected(start: Instant) {
+        ConnectTimings::with_current(|phases| {
+            let now = Instant::now();
+            let start = match phases.resolved {
+                Some(resolved) if resolved > start => resolved,
+                _ => start,
+            };
+            phases.connect = Some(phases.connect.unwrap_or_default() + (now - start));
+        });
+    }
+
+    #[cfg(any(feature = "__tls", feature = "socks"))]
+    fn add_connect(duration: Duration) {
+        ConnectTimings::with_current(|phases| {
+            phases.connect = Some(phases.connect.unwrap_or_default() + duration);
+        });
+    }
+
+    #[cfg(feature = "__tls")]
+    fn add_tls(duration: Duration) {
+        ConnectTimings::with_current(|phases| {
+            phases.tls = Some(phases.tls.unwrap_or_default() + duration);
+        });
+    }
+
+    fn ready(&self) {
+        self.0.lock().unwrap().ready = Some(Instant::now());
+    }
+
+    fn wrote(&self) {
+        self.0.lock().unwrap().last_write = Some(Instant::now());
+    }
+
+    // Reports the connection as reused, from its first response on.
+    fn pooled(&self) {
+        self.0.lock().unwrap().reported = true;
+    }
+
+    /// The timings of a request sent at `started`, whose response headers
+    /// were received at `headers_received`.
+    ///
+    /// Only the first response on a connection gets the connection phases.
+    pub(crate) fn request_timings(&self, started: Instant, headers_received: Instant) -> Timings {
+        let mut phases = self.0.lock().unwrap();
+        let reused = phases.reported;
+        phases.reported = true;
+
+        let sending = match phases.ready {
+            Some(ready) if ready > started => ready,
+            _ => started,
+        };
+        let sent = match phases.last_write {
+            Some(last_write) if last_write > sending => last_write,
+            _ => sending,
+        };
+
+        let mut timings = Timings {
+            request_sent: Some(sent - sending),
+            first_byte: Some(headers_received.saturating_duration_since(sent)),
+            reused,
+            ..Timings::default()
+        };
+        if !reused {
+            timings.dns = phases.dns;
+            timings.connect = phases.connect;
+            timings.tls = phases.tls;
+        }
+        timings
+    }
+}
+
+/// Fails writes that make no progress for too long.
+///
+/// Reads are timed by each request instead, as a connection may be idle,
+/// or carry several HTTP/2 streams.
+struct TimeoutConn {
+    inner: BoxConn,
+    write_timeout: IdleTimeout,
+}
+
+impl TimeoutConn {
+    fn poll_timeout<T>(
+        poll: Poll<io::Result<T>>,
+        timeout: &mut IdleTimeout,
+        cx: &mut Context,
+    ) -> Poll<io::Result<T>> {
+        match poll {
+            Poll::Ready(res) => {
+                timeout.reset();
+                Poll::Ready(res)
+            }
+            Poll::Pending => match timeout.poll_elapsed(cx) {
+                Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    crate::error::TimedOut,
+                ))),
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
+
+impl Connection for TimeoutConn {
+    fn connected(&self) -> Connected {
+        self.inner.connected()
+    }
+}
+
+impl AsyncRead for TimeoutConn {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8]
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+
+    unsafe fn prepare_uninitialized_buffer(
+        &self,
+        buf: &mut [MaybeUninit<u8>]
+    ) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl AsyncWrite for TimeoutConn {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8]
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        TimeoutConn::poll_timeout(poll, &mut this.write_timeout, cx)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_flush(cx);
+        TimeoutConn::poll_timeout(poll, &mut this.write_timeout, cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
     }
 }
 
+/// The URL of the proxy a connection was made through.
+///
+/// Added to the extensions of every response received on the connection.
+#[derive(Clone)]
+pub(crate) struct ProxyUrl(pub(crate) Url);
+
+/// The protocol negotiated with ALPN, when known.
+///
+/// Added to the extensions of every response received on the connection.
+#[derive(Clone)]
+pub(crate) struct NegotiatedAlpn(pub(crate) Vec<u8>);
+
 impl AsyncRead for Conn {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -560,6 +1689,11 @@ impl AsyncRead for Conn {
         buf: &mut [u8]
     ) -> Poll<io::Result<usize>> {
         let this = self.project();
+        if let Some(ref open) = this.open {
+            if open.poll_closed(cx) {
+                return Poll::Ready(Ok(0));
+            }
+        }
         AsyncRead::poll_read(this.inner, cx, buf)
     }
 
@@ -579,6 +1713,11 @@ impl AsyncRead for Conn {
             Self: Sized
     {
         let this = self.project();
+        if let Some(ref open) = this.open {
+            if open.poll_closed(cx) {
+                return Poll::Ready(Ok(0));
+            }
+        }
         AsyncRead::poll_read_buf(this.inner, cx, buf)
     }
 }
@@ -590,7 +1729,14 @@ impl AsyncWrite for Conn {
         buf: &[u8]
     ) -> Poll<Result<usize, io::Error>> {
         let this = self.project();
-        AsyncWrite::poll_write(this.inner, cx, buf)
+        if let Some(ref open) = this.open {
+            open.writing();
+        }
+        let poll = AsyncWrite::poll_write(this.inner, cx, buf);
+        if let (Poll::Ready(Ok(_)), Some(timings)) = (&poll, this.timings) {
+            timings.wrote();
+        }
+        poll
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
@@ -613,7 +1759,14 @@ impl AsyncWrite for Conn {
     ) -> Poll<Result<usize, io::Error>> where
         Self: Sized {
         let this = self.project();
-        AsyncWrite::poll_write_buf(this.inner, cx, buf)
+        if let Some(ref open) = this.open {
+            open.writing();
+        }
+        let poll = AsyncWrite::poll_write_buf(this.inner, cx, buf);
+        if let (Poll::Ready(Ok(_)), Some(timings)) = (&poll, this.timings) {
+            timings.wrote();
+        }
+        poll
     }
 }
 
@@ -703,24 +1856,183 @@ fn tunnel_eof() -> BoxError {
 
 #[cfg(feature = "default-tls")]
 mod native_tls_conn {
+    use std::io::{self, Read, Write};
     use std::mem::MaybeUninit;
-    use std::{pin::Pin, task::{Context, Poll}};
+    use std::{pin::Pin, task::{Context, Poll, Waker}};
     use bytes::{Buf, BufMut};
+    use futures_util::future::poll_fn;
     use hyper::client::connect::{Connected, Connection};
+    use native_tls_crate::{HandshakeError, MidHandshakeTlsStream, TlsConnector};
     use pin_project_lite::pin_project;
     use tokio::io::{AsyncRead, AsyncWrite};
-    use tokio_tls::TlsStream;
+
+    use super::NegotiatedAlpn;
+    use crate::tls::TlsInfo;
 
 
     pin_project! {
         pub(super) struct NativeTlsConn<T> {
             #[pin] pub(super) inner: TlsStream<T>,
+            pub(super) tls_info: bool,
         }
     }
 
     impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for NativeTlsConn<T> {
         fn connected(&self) -> Connected {
-            self.inner.get_ref().connected()
+            let io = self.inner.get_io();
+            let alpn = self.inner.get_ref().negotiated_alpn().ok().and_then(|alpn| alpn);
+            let connected = match alpn {
+                Some(ref alpn) if alpn == b"h2" => io
+                    .connected()
+                    .negotiated_h2()
+                    .extra(NegotiatedAlpn(alpn.clone())),
+                Some(ref alpn) => io.connected().extra(NegotiatedAlpn(alpn.clone())),
+                None => io.connected(),
+            };
+            if !self.tls_info {
+                return connected;
+            }
+
+            let cert = self.inner.get_ref().peer_certificate().ok().and_then(|cert| cert);
+            let der = cert.and_then(|cert| cert.to_der().ok());
+            connected.extra(TlsInfo {
+                peer_certificates: der.into_iter().collect(),
+                protocol_version: None,
+                cipher_suite: None,
+                alpn_protocol: alpn,
+            })
+        }
+    }
+
+    /// A native-tls stream over an async IO.
+    ///
+    /// This is `tokio_tls::TlsStream`, except that `get_ref` returns the
+    /// `native_tls::TlsStream`, for the peer certificate and the ALPN
+    /// protocol. `tokio_tls::TlsStream::get_ref` only returns the IO, and
+    /// the tokio-native-tls releases that return the `native_tls::TlsStream`
+    /// need tokio 0.3.
+    pub(super) struct TlsStream<S>(native_tls_crate::TlsStream<AllowStd<S>>);
+
+    /// Adapts an async IO to the `Read` and `Write` native-tls expects,
+    /// with `WouldBlock` errors for pending operations.
+    pub(super) struct AllowStd<S> {
+        inner: S,
+        waker: Option<Waker>,
+    }
+
+    /// Performs the TLS handshake for `domain` over `stream`.
+    pub(super) async fn connect<S>(
+        tls: &TlsConnector,
+        domain: &str,
+        stream: S,
+    ) -> Result<TlsStream<S>, native_tls_crate::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut start = Some(AllowStd { inner: stream, waker: None });
+        let mut mid: Option<MidHandshakeTlsStream<AllowStd<S>>> = None;
+        poll_fn(|cx| {
+            let handshake = match mid.take() {
+                None => {
+                    let mut stream = start.take().expect("polled after completion");
+                    stream.waker = Some(cx.waker().clone());
+                    tls.connect(domain, stream)
+                }
+                Some(mut mid) => {
+                    mid.get_mut().waker = Some(cx.waker().clone());
+                    mid.handshake()
+                }
+            };
+            match handshake {
+                Ok(stream) => Poll::Ready(Ok(TlsStream(stream))),
+                Err(HandshakeError::WouldBlock(handshaking)) => {
+                    mid = Some(handshaking);
+                    Poll::Pending
+                }
+                Err(HandshakeError::Failure(err)) => Poll::Ready(Err(err)),
+            }
+        })
+        .await
+    }
+
+    impl<S> TlsStream<S> {
+        pub(super) fn get_ref(&self) -> &native_tls_crate::TlsStream<AllowStd<S>> {
+            &self.0
+        }
+
+        pub(super) fn get_io(&self) -> &S {
+            &self.0.get_ref().inner
+        }
+
+        fn with_waker<F, R>(&mut self, cx: &mut Context, f: F) -> Poll<io::Result<R>>
+        where
+            F: FnOnce(&mut native_tls_crate::TlsStream<AllowStd<S>>) -> io::Result<R>,
+        {
+            self.0.get_mut().waker = Some(cx.waker().clone());
+            match f(&mut self.0) {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
+                res => Poll::Ready(res),
+            }
+        }
+    }
+
+    impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsStream<S> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8]
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().with_waker(cx, |stream| stream.read(buf))
+        }
+    }
+
+    impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for TlsStream<S> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8]
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().with_waker(cx, |stream| stream.write(buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            self.get_mut().with_waker(cx, |stream| stream.flush())
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+            self.get_mut().with_waker(cx, |stream| stream.shutdown())
+        }
+    }
+
+    fn waker(waker: &Option<Waker>) -> &Waker {
+        waker.as_ref().expect("TLS stream used outside of a task")
+    }
+
+    impl<S: AsyncRead + Unpin> Read for AllowStd<S> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut cx = Context::from_waker(waker(&self.waker));
+            match Pin::new(&mut self.inner).poll_read(&mut cx, buf) {
+                Poll::Ready(res) => res,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    impl<S: AsyncWrite + Unpin> Write for AllowStd<S> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut cx = Context::from_waker(waker(&self.waker));
+            match Pin::new(&mut self.inner).poll_write(&mut cx, buf) {
+                Poll::Ready(res) => res,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            let mut cx = Context::from_waker(waker(&self.waker));
+            match Pin::new(&mut self.inner).poll_flush(&mut cx) {
+                Poll::Ready(res) => res,
+                Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+            }
         }
     }
 
@@ -800,20 +2112,46 @@ mod rustls_tls_conn {
     use tokio::io::{AsyncRead, AsyncWrite};
     use tokio_rustls::client::TlsStream;
 
+    use super::NegotiatedAlpn;
+    use crate::tls::{TlsInfo, TlsVersion};
 
     pin_project! {
         pub(super) struct RustlsTlsConn<T> {
             #[pin] pub(super) inner: TlsStream<T>,
+            pub(super) tls_info: bool,
         }
     }
 
     impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for RustlsTlsConn<T> {
         fn connected(&self) -> Connected {
-            if self.inner.get_ref().1.get_alpn_protocol() == Some(b"h2") {
-                self.inner.get_ref().0.connected().negotiated_h2()
-            } else {
-                self.inner.get_ref().0.connected()
+            let (io, session) = self.inner.get_ref();
+            let connected = match session.get_alpn_protocol() {
+                Some(b"h2") => io
+                    .connected()
+                    .negotiated_h2()
+                    .extra(NegotiatedAlpn(b"h2".to_vec())),
+                Some(alpn) => io.connected().extra(NegotiatedAlpn(alpn.to_vec())),
+                None => io.connected(),
+            };
+            if !self.tls_info {
+                return connected;
             }
+
+            connected.extra(TlsInfo {
+                peer_certificates: session
+                    .get_peer_certificates()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|cert| cert.0)
+                    .collect(),
+                protocol_version: session
+                    .get_protocol_version()
+                    .and_then(TlsVersion::from_rustls),
+                cipher_suite: session
+                    .get_negotiated_ciphersuite()
+                    .map(|suite| format!("{:?}", suite.suite)),
+                alpn_protocol: session.get_alpn_protocol().map(|alpn| alpn.to_vec()),
+            })
         }
     }
 
@@ -882,16 +2220,243 @@ mod rustls_tls_conn {
     }
 }
 
+mod socket {
+    use std::future::Future;
+    use std::io;
+    use std::net::{IpAddr, SocketAddr};
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures_util::future::{self, Either};
+    use http::uri::Scheme;
+    use http::Uri;
+    use hyper::client::connect::dns::Name;
+    use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+    use tokio::net::TcpStream;
+
+    use super::HttpConnector;
+    use crate::error::BoxError;
+
+    pub(crate) type SocketConfig = dyn Fn(&Socket) -> io::Result<()> + Send + Sync;
+
+    /// How to set up the sockets of a client.
+    ///
+    /// hyper connects the sockets, unless they must be set up before they
+    /// are connected, for an interface, keepalive probes or a
+    /// `socket_config` function.
+    #[derive(Clone, Default)]
+    pub(crate) struct SocketOptions {
+        #[cfg(target_os = "linux")]
+        pub(crate) interface: Option<std::ffi::CString>,
+        pub(crate) keepalive: Option<Duration>,
+        pub(crate) keepalive_interval: Option<Duration>,
+        pub(crate) keepalive_retries: Option<u32>,
+        pub(crate) config: Option<Arc<SocketConfig>>,
+        // The hyper settings, for the sockets connected here.
+        pub(crate) local_address: Option<IpAddr>,
+        pub(crate) nodelay: bool,
+        pub(crate) happy_eyeballs_timeout: Option<Duration>,
+    }
+
+    impl SocketOptions {
+        pub(super) fn applies_before_connect(&self) -> bool {
+            #[cfg(target_os = "linux")]
+            {
+                if self.interface.is_some() {
+                    return true;
+                }
+            }
+            let probes = self.keepalive_interval.is_some() || self.keepalive_retries.is_some();
+            (self.keepalive.is_some() && probes) || self.config.is_some()
+        }
+
+        fn apply(&self, socket: &Socket) -> io::Result<()> {
+            #[cfg(target_os = "linux")]
+            {
+                if let Some(ref interface) = self.interface {
+                    socket.bind_device(Some(interface))?;
+                }
+            }
+            if let Some(keepalive) = self.keepalive {
+                socket.set_keepalive(Some(keepalive))?;
+                set_keepalive_probes(socket, self.keepalive_interval, self.keepalive_retries)?;
+            }
+            if let Some(ref config) = self.config {
+                config(socket)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Sets `TCP_KEEPINTVL` and `TCP_KEEPCNT`, which socket2 doesn't have.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ))]
+    fn set_keepalive_probes(
+        socket: &Socket,
+        interval: Option<Duration>,
+        retries: Option<u32>,
+    ) -> io::Result<()> {
+        use std::convert::TryFrom;
+        use std::os::unix::io::AsRawFd;
+
+        fn set(socket: &Socket, opt: libc::c_int, val: u64) -> io::Result<()> {
+            let val = libc::c_int::try_from(val)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+            let ret = unsafe {
+                libc::setsockopt(
+                    socket.as_raw_fd(),
+                    libc::IPPROTO_TCP,
+                    opt,
+                    &val as *const libc::c_int as *const libc::c_void,
+                    std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            if ret == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        if let Some(interval) = interval {
+            // In whole seconds, and at least one.
+            set(socket, libc::TCP_KEEPINTVL, interval.as_secs().max(1))?;
+        }
+        if let Some(retries) = retries {
+            set(socket, libc::TCP_KEEPCNT, u64::from(retries))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    )))]
+    fn set_keepalive_probes(_: &Socket, _: Option<Duration>, _: Option<u32>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Resolves `dst` and connects to it like hyper's `HttpConnector`, with
+    /// the sockets set up before connecting.
+    pub(super) async fn connect(
+        mut http: HttpConnector,
+        options: Arc<SocketOptions>,
+        dst: Uri,
+    ) -> Result<TcpStream, BoxError> {
+        let host = dst
+            .host()
+            .ok_or("no host in url")?
+            .trim_matches(|c| c == '[' || c == ']');
+        let port = match dst.port_u16() {
+            Some(port) => port,
+            None if dst.scheme() == Some(&Scheme::HTTPS) => 443,
+            None => 80,
+        };
+
+        let ips = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => http.resolve(host.parse::<Name>()?).await?.collect(),
+        };
+        let mut addrs = ips
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .filter(|addr| match options.local_address {
+                Some(local) => local.is_ipv4() == addr.is_ipv4(),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        // Like hyper, prefer the family of the first address, and race the
+        // other one after the Happy Eyeballs timeout.
+        let prefer_v6 = addrs.first().map(SocketAddr::is_ipv6).unwrap_or(false);
+        let fallback = {
+            let (preferred, fallback) = addrs.into_iter().partition(|a| a.is_ipv6() == prefer_v6);
+            addrs = preferred;
+            fallback
+        };
+
+        let preferred = connect_any(addrs, &options);
+        let delay = match options.happy_eyeballs_timeout {
+            Some(delay) if !fallback.is_empty() => delay,
+            _ => return Ok(preferred.await?),
+        };
+
+        futures_util::pin_mut!(preferred);
+        let preferred = match future::select(preferred, tokio::time::delay_for(delay)).await {
+            Either::Left((Ok(tcp), _)) => return Ok(tcp),
+            Either::Left((Err(_), _)) => return Ok(connect_any(fallback, &options).await?),
+            Either::Right((_, preferred)) => preferred,
+        };
+        let fallback = connect_any(fallback, &options);
+        futures_util::pin_mut!(fallback);
+        match future::select(preferred, fallback).await {
+            Either::Left((Ok(tcp), _)) | Either::Right((Ok(tcp), _)) => Ok(tcp),
+            Either::Left((Err(_), fallback)) => Ok(fallback.await?),
+            Either::Right((Err(_), preferred)) => Ok(preferred.await?),
+        }
+    }
+
+    fn connect_any<'a>(
+        addrs: Vec<SocketAddr>,
+        options: &'a SocketOptions,
+    ) -> Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send + 'a>> {
+        Box::pin(async move {
+            let mut err = None;
+            for addr in addrs {
+                log::debug!("connecting to {}", addr);
+                match connect_to(addr, options).await {
+                    Ok(tcp) => return Ok(tcp),
+                    Err(e) => {
+                        log::trace!("connect error for {}: {:?}", addr, e);
+                        err = Some(e);
+                    }
+                }
+            }
+            Err(err.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotConnected, "Network unreachable")
+            }))
+        })
+    }
+
+    async fn connect_to(addr: SocketAddr, options: &SocketOptions) -> io::Result<TcpStream> {
+        let domain = if addr.is_ipv4() {
+            Domain::ipv4()
+        } else {
+            Domain::ipv6()
+        };
+        let socket = Socket::new(domain, Type::stream(), Some(Protocol::tcp()))?;
+        if let Some(local) = options.local_address {
+            socket.bind(&SockAddr::from(SocketAddr::new(local, 0)))?;
+        }
+        options.apply(&socket)?;
+
+        let tcp = TcpStream::connect_std(socket.into_tcp_stream(), &addr).await?;
+        tcp.set_nodelay(options.nodelay)?;
+        Ok(tcp)
+    }
+}
+
 #[cfg(feature = "socks")]
 mod socks {
     use std::io;
     use std::net::ToSocketAddrs;
+    use std::sync::Arc;
 
     use http::Uri;
     use tokio::net::TcpStream;
     use tokio_socks::tcp::Socks5Stream;
 
-    use super::{BoxError, Scheme};
+    use super::{BoxError, HttpConnector, Scheme, SocketOptions};
     use crate::proxy::ProxyScheme;
 
     pub(super) enum DnsResolve {
@@ -899,16 +2464,22 @@ mod socks {
         Proxy,
     }
 
+    /// Connects to the SOCKS5 proxy like to any server, so the socket is set
+    /// up before it connects, then asks the proxy to connect to `dst`.
     pub(super) async fn connect(
         proxy: ProxyScheme,
         dst: Uri,
         dns: DnsResolve,
+        http: HttpConnector,
+        socket: Arc<SocketOptions>,
     ) -> Result<TcpStream, BoxError> {
         let https = dst.scheme() == Some(&Scheme::HTTPS);
         let original_host = dst
             .host()
             .ok_or(io::Error::new(io::ErrorKind::Other, "no host in url"))?;
-        let mut host = original_host.to_owned();
+        let mut host = original_host
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_owned();
         let port = match dst.port() {
             Some(p) => p.as_u16(),
             None if https => 443u16,
@@ -927,18 +2498,19 @@ mod socks {
             _ => unreachable!(),
         };
 
-        // Get a Tokio TcpStream
-        let stream = if let Some((username, password)) = auth {
-            Socks5Stream::connect_with_password(
-                socket_addr,
-                (host.as_str(), port),
-                &username,
-                &password,
-            )
+        let proxy_uri = format!("http://{}", socket_addr).parse::<Uri>()?;
+        let tcp = super::socket::connect(http, socket, proxy_uri)
             .await
-            .map_err(|e| format!("socks connect error: {}", e))?
+            .map_err(|e| format!("socks connect error: {}", e))?;
+        super::ConnectTimings::bound(tcp.local_addr().ok());
+
+        let target = (host.as_str(), port);
+        let stream = if let Some((username, password)) = auth {
+            Socks5Stream::connect_with_password_and_socket(tcp, target, &username, &password)
+                .await
+                .map_err(|e| format!("socks connect error: {}", e))?
         } else {
-            Socks5Stream::connect(socket_addr, (host.as_str(), port))
+            Socks5Stream::connect_with_socket(tcp, target)
                 .await
                 .map_err(|e| format!("socks connect error: {}", e))?
         };
@@ -1070,7 +2642,10 @@ mod tests {
     use crate::proxy;
     use std::io::{Read, Write};
     use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use std::thread;
+    use std::time::Duration;
     use tokio::net::TcpStream;
     use tokio::runtime;
 
@@ -1212,4 +2787,121 @@ mod tests {
 
         rt.block_on(f).unwrap();
     }
+
+    fn socket_connect(
+        options: super::SocketOptions,
+        addr: std::net::SocketAddr,
+    ) -> Result<TcpStream, crate::error::BoxError> {
+        let mut rt = runtime::Builder::new().basic_scheduler().enable_all().build().expect("new rt");
+        let http = super::HttpConnector::new_gai(None);
+        let dst = format!("http://{}", addr).parse().unwrap();
+        rt.block_on(super::socket::connect(http, Arc::new(options), dst))
+    }
+
+    #[test]
+    fn test_socket_config() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let called = Arc::new(AtomicUsize::new(0));
+        let counter = called.clone();
+        let options = super::SocketOptions {
+            config: Some(Arc::new(move |socket: &socket2::Socket| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                socket.set_recv_buffer_size(1 << 16)
+            })),
+            ..Default::default()
+        };
+        socket_connect(options, addr).unwrap();
+        assert_eq!(called.load(Ordering::SeqCst), 1);
+
+        let options = super::SocketOptions {
+            config: Some(Arc::new(|_: &socket2::Socket| {
+                Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            })),
+            ..Default::default()
+        };
+        let err = socket_connect(options, addr).unwrap_err();
+        let err = err.downcast_ref::<std::io::Error>().expect("io error");
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_socket_interface() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let options = super::SocketOptions {
+            interface: Some(std::ffi::CString::new("lo").unwrap()),
+            // Runs after the interface is bound.
+            config: Some(Arc::new(|socket: &socket2::Socket| {
+                use std::os::unix::io::AsRawFd;
+
+                // socket2's `Socket::device` reads the length wrong.
+                let mut buf = [0u8; libc::IFNAMSIZ];
+                let mut len = buf.len() as libc::socklen_t;
+                let ret = unsafe {
+                    libc::getsockopt(
+                        socket.as_raw_fd(),
+                        libc::SOL_SOCKET,
+                        libc::SO_BINDTODEVICE,
+                        buf.as_mut_ptr() as *mut libc::c_void,
+                        &mut len,
+                    )
+                };
+                assert_eq!(ret, 0);
+                assert_eq!(&buf[..len as usize], b"lo\0");
+                Ok(())
+            })),
+            ..Default::default()
+        };
+        socket_connect(options, addr).unwrap();
+
+        let options = super::SocketOptions {
+            interface: Some(std::ffi::CString::new("reqwest-none0").unwrap()),
+            ..Default::default()
+        };
+        socket_connect(options, addr).unwrap_err();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_socket_keepalive_probes() {
+        use std::os::unix::io::AsRawFd;
+
+        fn get(socket: &socket2::Socket, opt: libc::c_int) -> libc::c_int {
+            let mut val: libc::c_int = 0;
+            let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+            let ret = unsafe {
+                libc::getsockopt(
+                    socket.as_raw_fd(),
+                    libc::IPPROTO_TCP,
+                    opt,
+                    &mut val as *mut libc::c_int as *mut libc::c_void,
+                    &mut len,
+                )
+            };
+            assert_eq!(ret, 0);
+            val
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let options = super::SocketOptions {
+            keepalive: Some(Duration::from_secs(60)),
+            keepalive_interval: Some(Duration::from_secs(7)),
+            keepalive_retries: Some(3),
+            config: Some(Arc::new(|socket: &socket2::Socket| {
+                assert_eq!(socket.keepalive()?, Some(Duration::from_secs(60)));
+                assert_eq!(get(socket, libc::TCP_KEEPINTVL), 7);
+                assert_eq!(get(socket, libc::TCP_KEEPCNT), 3);
+                Ok(())
+            })),
+            ..Default::default()
+        };
+        assert!(options.applies_before_connect());
+        socket_connect(options, addr).unwrap();
+    }
 }
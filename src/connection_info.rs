@@ -0,0 +1,44 @@
+use std::net::SocketAddr;
+
+/// The connection a `Response` was received on.
+///
+/// Returned by `Response::connection_info`.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionInfo {
+    pub(crate) local_addr: Option<SocketAddr>,
+    pub(crate) remote_addr: Option<SocketAddr>,
+    pub(crate) reused: bool,
+    pub(crate) proxied: bool,
+    pub(crate) alpn_protocol: Option<Vec<u8>>,
+}
+
+impl ConnectionInfo {
+    /// The local address and port of the connection.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// The address the connection is made to, which is the address of the
+    /// proxy when one is used.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Returns true if the request was sent on a pooled connection.
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Returns true if the connection goes through a proxy.
+    pub fn is_proxied(&self) -> bool {
+        self.proxied
+    }
+
+    /// The protocol negotiated with ALPN during the TLS handshake, such as
+    /// `b"h2"`.
+    ///
+    /// `None` without TLS, or if no protocol was negotiated.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_ref().map(|p| &p[..])
+    }
+}
@@ -8,12 +8,13 @@ use hyper::client::connect::dns as hyper_dns;
 use hyper::service::Service;
 use tokio::sync::Mutex;
 use trust_dns_resolver::{
-    config::{ResolverConfig, ResolverOpts},
+    config::{LookupIpStrategy, ResolverConfig, ResolverOpts},
     lookup_ip::LookupIpIntoIter,
     system_conf, AsyncResolver, TokioConnection, TokioConnectionProvider,
 };
 
 use crate::error::BoxError;
+use crate::ip_family::IpFamily;
 
 type SharedResolver = Arc<AsyncResolver<TokioConnection, TokioConnectionProvider>>;
 
@@ -24,6 +25,7 @@ lazy_static! {
 #[derive(Clone)]
 pub(crate) struct TrustDnsResolver {
     state: Arc<Mutex<State>>,
+    family: Option<IpFamily>,
 }
 
 enum State {
@@ -32,7 +34,7 @@ enum State {
 }
 
 impl TrustDnsResolver {
-    pub(crate) fn new() -> io::Result<Self> {
+    pub(crate) fn new(family: Option<IpFamily>) -> io::Result<Self> {
         SYSTEM_CONF.as_ref().map_err(|e| {
             io::Error::new(e.kind(), format!("error reading DNS system conf: {}", e))
         })?;
@@ -42,6 +44,7 @@ impl TrustDnsResolver {
         // resolver.
         Ok(TrustDnsResolver {
             state: Arc::new(Mutex::new(State::Init)),
+            family,
         })
     }
 }
@@ -57,12 +60,13 @@ impl Service<hyper_dns::Name> for TrustDnsResolver {
 
     fn call(&mut self, name: hyper_dns::Name) -> Self::Future {
         let resolver = self.clone();
+        let family = self.family;
         Box::pin(async move {
             let mut lock = resolver.state.lock().await;
 
             let resolver = match &*lock {
                 State::Init => {
-                    let resolver = new_resolver(tokio::runtime::Handle::current()).await?;
+                    let resolver = new_resolver(family, tokio::runtime::Handle::current()).await?;
                     *lock = State::Ready(resolver.clone());
                     resolver
                 },
@@ -81,11 +85,22 @@ impl Service<hyper_dns::Name> for TrustDnsResolver {
 
 /// Takes a `Handle` argument as an indicator that it must be called from
 /// within the context of a Tokio runtime.
-async fn new_resolver(handle: tokio::runtime::Handle) -> Result<SharedResolver, BoxError> {
-    let (config, opts) = SYSTEM_CONF
+async fn new_resolver(
+    family: Option<IpFamily>,
+    handle: tokio::runtime::Handle,
+) -> Result<SharedResolver, BoxError> {
+    let (config, mut opts) = SYSTEM_CONF
         .as_ref()
         .expect("can't construct TrustDnsResolver if SYSTEM_CONF is error")
         .clone();
+    // Only look up the addresses of the allowed families, but all of them,
+    // so that the preferred family comes first.
+    match family {
+        Some(IpFamily::V4Only) => opts.ip_strategy = LookupIpStrategy::Ipv4Only,
+        Some(IpFamily::V6Only) => opts.ip_strategy = LookupIpStrategy::Ipv6Only,
+        Some(_) => opts.ip_strategy = LookupIpStrategy::Ipv4AndIpv6,
+        None => (),
+    }
     let resolver = AsyncResolver::new(config, opts, handle).await?;
     Ok(Arc::new(resolver))
 }
@@ -85,7 +85,50 @@ impl Error {
         let mut source = self.source();
 
         while let Some(err) = source {
-            if err.is::<TimedOut>() {
+            if err.is::<TimedOut>() || err.is::<PoolTimedOut>() {
+                return true;
+            }
+            // Read and write timeouts surface as I/O errors of the connection.
+            if let Some(io) = err.downcast_ref::<io::Error>() {
+                if io.get_ref().map(|inner| inner.is::<TimedOut>()).unwrap_or(false) {
+                    return true;
+                }
+            }
+            source = err.source();
+        }
+
+        false
+    }
+
+    /// Returns true if the request timed out while queued for a connection.
+    ///
+    /// This happens when the connection limits set with
+    /// `ClientBuilder::pool_max_connections_per_host` or
+    /// `ClientBuilder::pool_max_connections` are reached.
+    pub fn is_pool_timeout(&self) -> bool {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if err.is::<PoolTimedOut>() {
+                return true;
+            }
+            source = err.source();
+        }
+
+        false
+    }
+
+    /// Returns true if the server's public key didn't match the pins set
+    /// with `ClientBuilder::pin_certificate`.
+    ///
+    /// The error message includes the hash of the public key the server
+    /// presented.
+    #[cfg(feature = "__tls")]
+    pub fn is_certificate_pin(&self) -> bool {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if err.is::<crate::tls::PinMismatch>() {
                 return true;
             }
             source = err.source();
@@ -295,6 +338,17 @@ impl fmt::Display for TimedOut {
 
 impl StdError for TimedOut {}
 
+#[derive(Debug)]
+pub(crate) struct PoolTimedOut;
+
+impl fmt::Display for PoolTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("timed out waiting for a connection")
+    }
+}
+
+impl StdError for PoolTimedOut {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
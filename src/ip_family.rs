@@ -0,0 +1,79 @@
+use std::net::IpAddr;
+
+/// Which IP versions a `Client` connects with.
+///
+/// The addresses a host name resolves to are filtered or reordered by
+/// family, before connecting. With both families allowed, the addresses of
+/// the preferred family are tried first, and the other family only after
+/// the Happy Eyeballs timeout (see `ClientBuilder::happy_eyeballs_timeout`).
+///
+/// This doesn't apply to URLs with an IP address as host.
+///
+/// # Example
+///
+/// ```
+/// # fn run() -> Result<(), reqwest::Error> {
+/// let client = reqwest::Client::builder()
+///     .ip_family(reqwest::IpFamily::PreferV4)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpFamily {
+    /// Only connect to IPv4 addresses.
+    V4Only,
+    /// Only connect to IPv6 addresses.
+    V6Only,
+    /// Try IPv4 addresses first, then IPv6.
+    PreferV4,
+    /// Try IPv6 addresses first, then IPv4.
+    PreferV6,
+}
+
+impl IpFamily {
+    /// Filters and sorts resolved addresses, keeping the resolver order
+    /// within each family.
+    pub(crate) fn sort(self, addrs: impl Iterator<Item = IpAddr>) -> Vec<IpAddr> {
+        let (v4, v6): (Vec<_>, Vec<_>) = addrs.partition(IpAddr::is_ipv4);
+        match self {
+            IpFamily::V4Only => v4,
+            IpFamily::V6Only => v6,
+            IpFamily::PreferV4 => v4.into_iter().chain(v6).collect(),
+            IpFamily::PreferV6 => v6.into_iter().chain(v4).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort() {
+        let addrs: Vec<IpAddr> = vec![
+            "2001:db8::1".parse().unwrap(),
+            "192.0.2.1".parse().unwrap(),
+            "2001:db8::2".parse().unwrap(),
+            "192.0.2.2".parse().unwrap(),
+        ];
+        let sorted = |family: IpFamily| {
+            family
+                .sort(addrs.iter().cloned())
+                .into_iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(IpFamily::V4Only), ["192.0.2.1", "192.0.2.2"]);
+        assert_eq!(sorted(IpFamily::V6Only), ["2001:db8::1", "2001:db8::2"]);
+        assert_eq!(
+            sorted(IpFamily::PreferV4),
+            ["192.0.2.1", "192.0.2.2", "2001:db8::1", "2001:db8::2"]
+        );
+        assert_eq!(
+            sorted(IpFamily::PreferV6),
+            ["2001:db8::1", "2001:db8::2", "192.0.2.1", "192.0.2.2"]
+        );
+    }
+}
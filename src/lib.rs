@@ -170,6 +170,11 @@
 //! - **native-tls**: Enables TLS functionality provided by `native-tls`.
 //! - **native-tls-vendored**: Enables the `vendored` feature of `native-tls`.
 //! - **rustls-tls**: Enables TLS functionality provided by `rustls`.
+//! - **rustls-tls-native-roots**: Makes `rustls` trust the root certificates
+//!   of the operating system instead of the bundled `webpki-roots`.
+//! - **encrypted-pkcs8**: Provides `Identity::from_encrypted_pkcs8_pem`. Its
+//!   `pkcs8` dependency needs Rust 1.65 or newer, above the minimum supported
+//!   Rust version of the rest of the crate.
 //! - **blocking**: Provides the [blocking][] client API.
 //! - **cookies**: Provides cookie session support.
 //! - **gzip**: Provides response body gzip decompression.
@@ -177,6 +182,11 @@
 //! - **json**: Provides serialization and deserialization for JSON bodies.
 //! - **stream**: Adds support for `futures::Stream`.
 //! - **socks**: Provides SOCKS5 proxy support.
+//! - **pac**: Provides proxy auto-config (PAC) script support. Its
+//!   JavaScript engine, `boa_engine`, needs Rust 1.74 or newer, above the
+//!   minimum supported Rust version of the rest of the crate.
+//! - **tracing**: Emits `tracing` spans for requests, redirects, connects,
+//!   TLS handshakes and proxy tunnels.
 //! - **trust-dns**: Enables a trust-dns async resolver instead of default
 //!   threadpool using `getaddrinfo`.
 //!
@@ -278,30 +288,52 @@ if_hyper! {
     #[macro_use]
     extern crate lazy_static;
 
+    #[cfg(feature = "tracing")]
+    extern crate tracing_crate as tracing;
+
     #[cfg(test)]
     doctest!("../README.md");
 
     pub use self::async_impl::{
         multipart, Body, Client, ClientBuilder, Request, RequestBuilder, Response, ResponseBuilderExt,
     };
+    pub use self::connection_info::ConnectionInfo;
+    pub use self::ip_family::IpFamily;
+    pub use self::pool::{HostStats, PoolStats};
     pub use self::proxy::Proxy;
+    pub use self::proxy_protocol::ProxyProtocol;
+    pub use self::timings::Timings;
     #[cfg(feature = "__tls")]
-    pub use self::tls::{Certificate, Identity};
+    pub use self::tls::{
+        Certificate, HostTlsConfig, Identity, ServerCertInfo, TlsInfo, TlsSessionCache,
+        TlsVersion,
+    };
 
 
+    #[macro_use]
+    mod trace;
+
     mod async_impl;
     #[cfg(feature = "blocking")]
     pub mod blocking;
     mod connect;
+    mod connection_info;
     #[cfg(feature = "cookies")]
     pub mod cookie;
     #[cfg(feature = "trust-dns")]
     mod dns;
+    mod ip_family;
+    mod pool;
+    pub mod propagation;
     mod proxy;
+    mod proxy_protocol;
+    mod timings;
     pub mod redirect;
     #[cfg(feature = "__tls")]
     mod tls;
     mod util;
+    #[cfg(feature = "__tls")]
+    mod x509;
 }
 
 if_wasm! {
@@ -0,0 +1,750 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use http::Uri;
+
+/// A snapshot of the connections of a `Client`, by host.
+///
+/// Returned by `Client::pool_stats`.
+#[derive(Clone, Debug, Default)]
+pub struct PoolStats {
+    hosts: HashMap<String, HostStats>,
+}
+
+/// The connections of a `Client` to a single host.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HostStats {
+    idle: usize,
+    active: usize,
+}
+
+impl PoolStats {
+    /// The connections to `host`, given as `scheme://host:port` with the
+    /// port only when the URL has one, such as `"https://hyper.rs"`.
+    pub fn host(&self, host: &str) -> Option<HostStats> {
+        self.hosts.get(host).copied()
+    }
+
+    /// Iterates over the hosts that have connections, or requests in flight.
+    pub fn hosts(&self) -> impl Iterator<Item = (&str, HostStats)> {
+        self.hosts.iter().map(|(host, stats)| (host.as_str(), *stats))
+    }
+
+    /// The number of idle connections to all hosts.
+    pub fn idle(&self) -> usize {
+        self.hosts.values().map(|stats| stats.idle).sum()
+    }
+
+    /// The number of connections in use, to all hosts.
+    pub fn active(&self) -> usize {
+        self.hosts.values().map(|stats| stats.active).sum()
+    }
+}
+
+impl HostStats {
+    /// The number of open connections waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.idle
+    }
+
+    /// The number of open connections in use by a request.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+}
+
+/// Counts the open connections and the requests in flight of a client.
+///
+/// hyper doesn't expose its pool, so a connection is considered active when
+/// a request to its host is in flight, until the response body is done.
+///
+/// The connection limits are enforced by the connector, which waits for
+/// room before opening a connection. Waiting connections are served in the
+/// order they started, and a connection closing only wakes the one that
+/// takes its place. When only the total limit keeps a connection waiting,
+/// an idle connection to another host is closed to make room.
+#[derive(Clone, Default)]
+pub(crate) struct PoolTracker(Arc<Mutex<Tracked>>);
+
+#[derive(Default)]
+struct Tracked {
+    hosts: HashMap<String, Counts>,
+    open: usize,
+    in_flight: usize,
+    max_per_host: Option<usize>,
+    max_total: Option<usize>,
+    // Connections waiting for room under the limits, oldest first.
+    waiting: VecDeque<Waiting>,
+    next_id: u64,
+    conns: HashMap<u64, Arc<ConnState>>,
+    // Connections being closed to make room, until they are dropped.
+    closing: usize,
+    idle_waiters: Vec<Waker>,
+    shut_down: bool,
+}
+
+#[derive(Default)]
+struct Counts {
+    open: usize,
+    in_flight: usize,
+    waiting: usize,
+}
+
+struct Waiting {
+    id: u64,
+    key: String,
+    waker: Waker,
+    // Room was made for it, and is kept until it is polled.
+    granted: bool,
+}
+
+/// Tracks an open connection until dropped.
+pub(crate) struct OpenConn {
+    tracker: PoolTracker,
+    id: u64,
+    state: Arc<ConnState>,
+}
+
+/// Whether an open connection is idle, shared with the responses read from
+/// it.
+///
+/// A connection is busy from its first write until the responses read from
+/// it are done, without writing since. Only an idle connection is closed to
+/// make room, by making its next read see the end of the stream, which
+/// hyper takes as the server closing it.
+struct ConnState {
+    key: String,
+    // The number of writes so far, shifted left by 2, and `IDLE`, `BUSY`,
+    // `CLOSING` or `CLOSED`.
+    state: AtomicUsize,
+    responses: AtomicUsize,
+    // Wakes the connection to read the end of the stream.
+    reader: Mutex<Option<Waker>>,
+}
+
+const IDLE: usize = 0;
+const BUSY: usize = 1;
+const CLOSING: usize = 2;
+const CLOSED: usize = 3;
+const STATE: usize = 3;
+
+/// Identifies the connection a response was received on.
+#[derive(Clone)]
+pub(crate) struct ConnHandle(Arc<ConnState>);
+
+/// Waits for room to open a connection under the limits.
+pub(crate) struct Opening {
+    tracker: PoolTracker,
+    key: String,
+    id: Option<u64>,
+    // The request the connection is opened for, if any.
+    ticket: Option<Ticket>,
+}
+
+/// Counts the connections a request waits for room to open.
+///
+/// hyper opens a connection for a request while polling it, and moves the
+/// connect to the background when the request gets another connection
+/// first. A waiting connection polled without its request is given up, so
+/// that it doesn't keep its place in the queue.
+#[derive(Clone, Default)]
+pub(crate) struct Ticket(Arc<AtomicUsize>);
+
+thread_local! {
+    // The ticket of the request being polled. A `const` initializer needs a
+    // newer Rust than the crate supports.
+    #[allow(clippy::missing_const_for_thread_local)]
+    static POLLING: RefCell<Option<Ticket>> = RefCell::new(None);
+}
+
+// Restores the ticket polled before.
+struct Polling(Option<Ticket>);
+
+/// Tracks a request in flight until dropped.
+pub(crate) struct InFlight {
+    tracker: PoolTracker,
+    key: String,
+    // The connection of the response, and its writes when it was received.
+    conn: Option<(Arc<ConnState>, usize)>,
+}
+
+pub(crate) fn key(uri: &Uri) -> String {
+    format!(
+        "{}://{}",
+        uri.scheme_str().unwrap_or("http"),
+        uri.authority().map(|a| a.as_str()).unwrap_or("")
+    )
+}
+
+impl PoolTracker {
+    pub(crate) fn new(max_per_host: Option<usize>, max_total: Option<usize>) -> PoolTracker {
+        let tracker = PoolTracker::default();
+        {
+            let mut tracked = tracker.0.lock().unwrap();
+            tracked.max_per_host = max_per_host;
+            tracked.max_total = max_total;
+        }
+        tracker
+    }
+
+    pub(crate) fn stats(&self) -> PoolStats {
+        let tracked = self.0.lock().unwrap();
+        let hosts = tracked
+            .hosts
+            .iter()
+            .map(|(host, counts)| {
+                let active = counts.in_flight.min(counts.open);
+                let stats = HostStats {
+                    idle: counts.open - active,
+                    active,
+                };
+                (host.clone(), stats)
+            })
+            .collect();
+        PoolStats { hosts }
+    }
+
+    /// Opens a connection to `uri`, once the connection limits allow it.
+    ///
+    /// This fails if the client is shut down while waiting.
+    pub(crate) fn open(&self, uri: &Uri) -> Opening {
+        Opening {
+            tracker: self.clone(),
+            key: key(uri),
+            id: None,
+            ticket: POLLING.with(|polling| polling.borrow().clone()),
+        }
+    }
+
+    /// Starts a request to the host of `key`.
+    pub(crate) fn in_flight(&self, key: &str) -> InFlight {
+        let mut tracked = self.0.lock().unwrap();
+        tracked.update(key, |counts| counts.in_flight += 1);
+        InFlight {
+            tracker: self.clone(),
+            key: key.to_owned(),
+            conn: None,
+        }
+    }
+
+    pub(crate) fn shut_down(&self) {
+        let mut tracked = self.0.lock().unwrap();
+        tracked.shut_down = true;
+        for waiting in &tracked.waiting {
+            waiting.waker.wake_by_ref();
+        }
+    }
+
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.0.lock().unwrap().shut_down
+    }
+
+    /// Resolves once no request is in flight.
+    pub(crate) fn idle(&self) -> impl Future<Output = ()> {
+        let tracker = self.clone();
+        futures_util::future::poll_fn(move |cx| {
+            let mut tracked = tracker.0.lock().unwrap();
+            if tracked.in_flight == 0 {
+                return Poll::Ready(());
+            }
+            if !tracked.idle_waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                tracked.idle_waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+}
+
+impl Tracked {
+    fn has_room(&self, key: &str) -> bool {
+        if let Some(max) = self.max_total {
+            if self.open >= max {
+                return false;
+            }
+        }
+        self.has_host_room(key)
+    }
+
+    fn has_host_room(&self, key: &str) -> bool {
+        match (self.max_per_host, self.hosts.get(key)) {
+            (Some(max), Some(counts)) => counts.open < max,
+            _ => true,
+        }
+    }
+
+    fn update<F: FnOnce(&mut Counts)>(&mut self, key: &str, f: F) {
+        let counts = self.hosts.entry(key.to_owned()).or_default();
+        let (open, in_flight) = (counts.open, counts.in_flight);
+        f(counts);
+        self.open = self.open + counts.open - open;
+        self.in_flight = self.in_flight + counts.in_flight - in_flight;
+        let closed = counts.open < open;
+        if counts.open == 0 && counts.in_flight == 0 && counts.waiting == 0 {
+            self.hosts.remove(key);
+        }
+
+        if closed {
+            self.grant();
+        }
+        if in_flight > 0 && self.in_flight == 0 {
+            for waker in self.idle_waiters.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Makes room for the waiting connections that fit under the limits,
+    /// oldest first, and wakes them.
+    fn grant(&mut self) {
+        for i in 0..self.waiting.len() {
+            if self.waiting[i].granted || !self.has_room(&self.waiting[i].key) {
+                continue;
+            }
+            let key = self.waiting[i].key.clone();
+            self.update(&key, |counts| {
+                counts.open += 1;
+                counts.waiting -= 1;
+            });
+            self.waiting[i].granted = true;
+            self.waiting[i].waker.wake_by_ref();
+        }
+        self.close_idle();
+    }
+
+    /// Closes idle connections to the hosts nothing waits for, to make room
+    /// for the waiting connections that only the total limit holds back.
+    fn close_idle(&mut self) {
+        match self.max_total {
+            Some(max) if self.open >= max && !self.shut_down => (),
+            _ => return,
+        }
+        let held = self
+            .waiting
+            .iter()
+            .filter(|waiting| !waiting.granted && self.has_host_room(&waiting.key))
+            .count();
+        let mut needed = held.saturating_sub(self.closing);
+        for conn in self.conns.values() {
+            if needed == 0 {
+                break;
+            }
+            let waited_for = self.hosts.get(&conn.key).map(|counts| counts.waiting > 0);
+            if waited_for == Some(true) || !conn.close() {
+                continue;
+            }
+            log::debug!("closing an idle connection to {} to make room", conn.key);
+            self.closing += 1;
+            needed -= 1;
+        }
+    }
+}
+
+impl Ticket {
+    /// Polls a request with `f`, so that the connections opened for it
+    /// count on this ticket.
+    pub(crate) fn poll<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        let _polling = Polling(POLLING.with(|polling| polling.replace(Some(self.clone()))));
+        f()
+    }
+
+    /// Whether a connection opened for the request is waiting for room.
+    pub(crate) fn is_waiting(&self) -> bool {
+        self.0.load(Ordering::Acquire) > 0
+    }
+
+    fn is_polling(&self) -> bool {
+        POLLING.with(|polling| {
+            polling.borrow().as_ref().map(|ticket| Arc::ptr_eq(&ticket.0, &self.0)) == Some(true)
+        })
+    }
+}
+
+impl Drop for Polling {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        POLLING.with(|polling| *polling.borrow_mut() = previous);
+    }
+}
+
+impl Future for Opening {
+    type Output = Result<OpenConn, crate::error::BoxError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let abandoned = this.id.is_some()
+            && this.ticket.as_ref().map(|ticket| !ticket.is_polling()) == Some(true);
+        if abandoned {
+            log::debug!("giving up on opening a connection to {}, no longer needed", this.key);
+            this.dequeue();
+            return Poll::Ready(Err("connection no longer needed".into()));
+        }
+
+        let mut tracked = this.tracker.0.lock().unwrap();
+        let granted = match this.id {
+            Some(id) => {
+                let i = tracked
+                    .waiting
+                    .iter()
+                    .position(|waiting| waiting.id == id)
+                    .expect("waiting connection is queued");
+                if tracked.waiting[i].granted {
+                    tracked.waiting.remove(i);
+                    if let Some(ref ticket) = this.ticket {
+                        ticket.0.fetch_sub(1, Ordering::AcqRel);
+                    }
+                    true
+                } else {
+                    tracked.waiting[i].waker = cx.waker().clone();
+                    false
+                }
+            }
+            None => {
+                let queued =
+                    tracked.hosts.get(&this.key).map(|counts| counts.waiting > 0) == Some(true);
+                if !queued && tracked.has_room(&this.key) && !tracked.shut_down {
+                    tracked.update(&this.key, |counts| counts.open += 1);
+                    true
+                } else {
+                    let id = tracked.next_id;
+                    tracked.next_id += 1;
+                    tracked.update(&this.key, |counts| counts.waiting += 1);
+                    tracked.waiting.push_back(Waiting {
+                        id,
+                        key: this.key.clone(),
+                        waker: cx.waker().clone(),
+                        granted: false,
+                    });
+                    this.id = Some(id);
+                    if let Some(ref ticket) = this.ticket {
+                        ticket.0.fetch_add(1, Ordering::AcqRel);
+                    }
+                    false
+                }
+            }
+        };
+
+        if granted {
+            this.id = None;
+            // Dropping the tracked connection makes the room available again.
+            let id = tracked.next_id;
+            tracked.next_id += 1;
+            let state = Arc::new(ConnState {
+                key: this.key.split_off(0),
+                state: AtomicUsize::new(BUSY),
+                responses: AtomicUsize::new(0),
+                reader: Mutex::new(None),
+            });
+            tracked.conns.insert(id, state.clone());
+            return Poll::Ready(Ok(OpenConn {
+                tracker: this.tracker.clone(),
+                id,
+                state,
+            }));
+        }
+        if tracked.shut_down {
+            return Poll::Ready(Err("client is shut down".into()));
+        }
+        tracked.close_idle();
+        Poll::Pending
+    }
+}
+
+impl Opening {
+    /// Leaves the queue, passing on the room if it was made.
+    fn dequeue(&mut self) {
+        let id = match self.id.take() {
+            Some(id) => id,
+            None => return,
+        };
+        let mut tracked = self.tracker.0.lock().unwrap();
+        let i = match tracked.waiting.iter().position(|waiting| waiting.id == id) {
+            Some(i) => i,
+            None => return,
+        };
+        let waiting = tracked.waiting.remove(i).expect("position is in bounds");
+        if let Some(ref ticket) = self.ticket {
+            ticket.0.fetch_sub(1, Ordering::AcqRel);
+        }
+        if waiting.granted {
+            // Pass the room on.
+            tracked.update(&self.key, |counts| counts.open -= 1);
+        } else {
+            tracked.update(&self.key, |counts| counts.waiting -= 1);
+        }
+    }
+}
+
+impl Drop for Opening {
+    fn drop(&mut self) {
+        self.dequeue();
+    }
+}
+
+impl OpenConn {
+    pub(crate) fn handle(&self) -> ConnHandle {
+        ConnHandle(self.state.clone())
+    }
+
+    /// Marks the connection idle, before hyper first uses it.
+    pub(crate) fn idle(&self) {
+        if self.state.transition(BUSY, IDLE) {
+            self.tracker.0.lock().unwrap().close_idle();
+        }
+    }
+
+    /// Whether the connection is closed to make room, which its reads end.
+    ///
+    /// Otherwise, the read waits to be woken when it is.
+    pub(crate) fn poll_closed(&self, cx: &mut Context<'_>) -> bool {
+        *self.state.reader.lock().unwrap() = Some(cx.waker().clone());
+        self.state.transition(CLOSING, CLOSED)
+            || self.state.state.load(Ordering::Acquire) & STATE == CLOSED
+    }
+
+    /// Marks the connection busy before writing to it.
+    ///
+    /// A connection written to before reading that it's closed stays open,
+    /// and another one is closed instead.
+    pub(crate) fn writing(&self) {
+        let mut state = self.state.state.load(Ordering::Acquire);
+        loop {
+            if state & STATE == CLOSED {
+                return;
+            }
+            let next = ((state & !STATE) + (1 << 2)) | BUSY;
+            match self
+                .state
+                .state
+                .compare_exchange_weak(state, next, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(actual) => state = actual,
+            }
+        }
+        if state & STATE == CLOSING {
+            let mut tracked = self.tracker.0.lock().unwrap();
+            tracked.closing -= 1;
+            tracked.close_idle();
+        }
+    }
+}
+
+impl ConnState {
+    /// Starts closing the connection if it's idle.
+    fn close(&self) -> bool {
+        let closing = self.transition(IDLE, CLOSING);
+        if closing {
+            if let Some(reader) = self.reader.lock().unwrap().take() {
+                reader.wake();
+            }
+        }
+        closing
+    }
+
+    fn transition(&self, from: usize, to: usize) -> bool {
+        let state = self.state.load(Ordering::Acquire);
+        state & STATE == from
+            && self
+                .state
+                .compare_exchange(state, (state & !STATE) | to, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+    }
+}
+
+impl InFlight {
+    /// Ties the request to the connection its response is received on, so
+    /// that the connection is idle again once the response is done.
+    pub(crate) fn bind(&mut self, conn: &ConnHandle) {
+        if self.conn.is_some() {
+            return;
+        }
+        conn.0.responses.fetch_add(1, Ordering::AcqRel);
+        let writes = conn.0.state.load(Ordering::Acquire) & !STATE;
+        self.conn = Some((conn.0.clone(), writes));
+    }
+}
+
+impl Drop for OpenConn {
+    fn drop(&mut self) {
+        let mut tracked = self.tracker.0.lock().unwrap();
+        tracked.conns.remove(&self.id);
+        let state = self.state.state.load(Ordering::Acquire) & STATE;
+        if state == CLOSING || state == CLOSED {
+            tracked.closing -= 1;
+        }
+        tracked.update(&self.state.key, |counts| counts.open -= 1);
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut tracked = self.tracker.0.lock().unwrap();
+        if let Some((conn, writes)) = self.conn.take() {
+            // Unless it was written to since, for another request.
+            let idle = conn.responses.fetch_sub(1, Ordering::AcqRel) == 1
+                && conn
+                    .state
+                    .compare_exchange(writes | BUSY, writes | IDLE, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok();
+            if idle {
+                tracked.close_idle();
+            }
+        }
+        tracked.update(&self.key, |counts| counts.in_flight -= 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::task::noop_waker_ref;
+
+    fn poll(opening: &mut Opening) -> Option<OpenConn> {
+        match Pin::new(opening).poll(&mut Context::from_waker(noop_waker_ref())) {
+            Poll::Ready(Ok(conn)) => Some(conn),
+            Poll::Ready(Err(err)) => panic!("open failed: {}", err),
+            Poll::Pending => None,
+        }
+    }
+
+    fn open(tracker: &PoolTracker, uri: &str) -> (Opening, Option<OpenConn>) {
+        let mut opening = tracker.open(&uri.parse().unwrap());
+        let conn = poll(&mut opening);
+        (opening, conn)
+    }
+
+    #[test]
+    fn test_stats() {
+        let tracker = PoolTracker::default();
+        let uri: Uri = "http://hyper.rs/guides".parse().unwrap();
+
+        let conn = open(&tracker, "http://hyper.rs/").1.unwrap();
+        let active = tracker.in_flight(&key(&uri));
+        let other = open(&tracker, "http://hyper.rs/").1.unwrap();
+        let stats = tracker.stats().host("http://hyper.rs").unwrap();
+        assert_eq!((stats.idle(), stats.active()), (1, 1));
+
+        drop(active);
+        assert_eq!(tracker.stats().idle(), 2);
+        assert_eq!(tracker.stats().active(), 0);
+
+        drop(conn);
+        drop(other);
+        assert_eq!(tracker.stats().hosts().count(), 0);
+    }
+
+    #[test]
+    fn test_limits() {
+        let tracker = PoolTracker::new(Some(1), Some(2));
+
+        let a = open(&tracker, "http://a/").1.unwrap();
+        let ticket = Ticket::default();
+        let (mut a2, conn) = ticket.poll(|| open(&tracker, "http://a/"));
+        assert!(conn.is_none());
+        assert!(ticket.is_waiting());
+
+        let b = open(&tracker, "http://b/").1.unwrap();
+        // the global limit is reached
+        let (mut c, conn) = open(&tracker, "http://c/");
+        assert!(conn.is_none());
+
+        // requests in flight don't take room, connections do
+        let _in_flight = tracker.in_flight("http://a");
+        drop(b);
+        assert!(ticket.poll(|| poll(&mut a2)).is_none());
+        assert!(poll(&mut c).is_some());
+
+        drop(a);
+        assert!(ticket.poll(|| poll(&mut a2)).is_some());
+        assert!(!ticket.is_waiting());
+    }
+
+    #[test]
+    fn test_closes_idle_for_other_host() {
+        let tracker = PoolTracker::new(None, Some(2));
+        let cx = &mut Context::from_waker(noop_waker_ref());
+
+        let a = open(&tracker, "http://a/").1.unwrap();
+        let a2 = open(&tracker, "http://a/").1.unwrap();
+        let (mut b, conn) = open(&tracker, "http://b/");
+        assert!(conn.is_none());
+        assert!(!a.poll_closed(cx));
+
+        // only an idle connection is closed
+        a.idle();
+        assert!(a.poll_closed(cx));
+        assert!(!a2.poll_closed(cx));
+        assert!(poll(&mut b).is_none());
+        drop(a);
+        let _b = poll(&mut b).unwrap();
+
+        // written to before reading the end, it stays open
+        let (mut c, _) = open(&tracker, "http://c/");
+        a2.idle();
+        a2.writing();
+        assert!(!a2.poll_closed(cx));
+        assert!(poll(&mut c).is_none());
+    }
+
+    #[test]
+    fn test_waiting_in_order() {
+        let tracker = PoolTracker::new(Some(1), None);
+
+        let a = open(&tracker, "http://a/").1.unwrap();
+        let (mut first, _) = open(&tracker, "http://a/");
+        let (mut second, _) = open(&tracker, "http://a/");
+
+        drop(a);
+        assert!(poll(&mut second).is_none());
+        let first = poll(&mut first).unwrap();
+
+        // giving up on waiting passes the room on
+        let (third, _) = open(&tracker, "http://a/");
+        drop(first);
+        drop(second);
+        drop(third);
+        assert_eq!(tracker.stats().hosts().count(), 0);
+    }
+
+    #[test]
+    fn test_abandoned_while_waiting() {
+        let tracker = PoolTracker::new(Some(1), None);
+
+        let a = open(&tracker, "http://a/").1.unwrap();
+        let ticket = Ticket::default();
+        let (mut abandoned, _) = ticket.poll(|| open(&tracker, "http://a/"));
+        let (mut next, _) = open(&tracker, "http://a/");
+        assert!(ticket.is_waiting());
+
+        // polled without its request, the connection gives up its place
+        let res = Pin::new(&mut abandoned).poll(&mut Context::from_waker(noop_waker_ref()));
+        match res {
+            Poll::Ready(Err(_)) => (),
+            _ => panic!("expected the abandoned connection to give up"),
+        }
+        assert!(!ticket.is_waiting());
+
+        drop(a);
+        assert!(poll(&mut next).is_some());
+    }
+
+    #[test]
+    fn test_shut_down_while_waiting() {
+        let tracker = PoolTracker::new(Some(1), None);
+
+        let _a = open(&tracker, "http://a/").1.unwrap();
+        let (mut waiting, _) = open(&tracker, "http://a/");
+        tracker.shut_down();
+
+        let res = Pin::new(&mut waiting).poll(&mut Context::from_waker(noop_waker_ref()));
+        match res {
+            Poll::Ready(Err(_)) => (),
+            _ => panic!("expected the shut down client to fail"),
+        }
+    }
+}
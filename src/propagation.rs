@@ -0,0 +1,358 @@
+//! Trace context propagation
+//!
+//! A `Client` can add the headers of a distributed tracing system to every
+//! request it sends, including each redirect hop, so that the trace of the
+//! caller continues in the services it calls. To enable it, pass a
+//! `propagation::Propagation` to `ClientBuilder::propagation`.
+//!
+//! A `Propagation` combines a function returning the current `SpanContext`,
+//! typically read from the tracing library of the application, with a
+//! `Propagator` that writes it into the request headers. Propagators are
+//! provided for [W3C Trace Context][w3c] and [B3][b3].
+//!
+//! When the `tracing` feature is enabled, the function is called inside the
+//! span of the request or redirect hop being sent, and
+//! `Propagation::from_span` passes it that span, for the tracing layer of a
+//! distributed tracing system to read the context from.
+//!
+//! Headers set on the request by the caller are left alone: if it already
+//! has any of the headers the propagator writes, nothing is injected.
+//!
+//! [w3c]: https://www.w3.org/TR/trace-context/
+//! [b3]: https://github.com/openzipkin/b3-propagation
+
+use std::fmt;
+
+use crate::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// Injects trace context headers into each request sent by a `Client`.
+pub struct Propagation {
+    propagator: Box<dyn Propagator>,
+    current: Box<dyn Fn() -> Option<SpanContext> + Send + Sync>,
+}
+
+/// Writes a `SpanContext` into the headers of a request.
+pub trait Propagator: Send + Sync + 'static {
+    /// Add the headers carrying `context` to `headers`.
+    fn inject(&self, context: &SpanContext, headers: &mut HeaderMap);
+}
+
+/// The identity of a span, as sent to other services.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpanContext {
+    trace_id: u128,
+    span_id: u64,
+    sampled: bool,
+    trace_state: Option<String>,
+}
+
+/// The [W3C Trace Context][spec] propagator.
+///
+/// Sends the `traceparent` header, and the `tracestate` header when the
+/// context has a trace state.
+///
+/// [spec]: https://www.w3.org/TR/trace-context/
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceContext;
+
+/// The [B3][spec] propagator used by Zipkin.
+///
+/// [spec]: https://github.com/openzipkin/b3-propagation
+#[derive(Clone, Copy, Debug)]
+pub struct B3 {
+    single: bool,
+}
+
+impl Propagation {
+    /// Create a `Propagation` injecting the context returned by `current`
+    /// with `propagator`.
+    ///
+    /// Nothing is injected when `current` returns `None`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use reqwest::propagation::{Propagation, SpanContext, TraceContext};
+    ///
+    /// # fn current_trace() -> Option<(u128, u64)> { None }
+    /// # fn run() -> Result<(), reqwest::Error> {
+    /// let propagation = Propagation::new(TraceContext, || {
+    ///     let (trace_id, span_id) = current_trace()?;
+    ///     Some(SpanContext::new(trace_id, span_id))
+    /// });
+    /// let client = reqwest::Client::builder()
+    ///     .propagation(propagation)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new<P, F>(propagator: P, current: F) -> Propagation
+    where
+        P: Propagator,
+        F: Fn() -> Option<SpanContext> + Send + Sync + 'static,
+    {
+        Propagation {
+            propagator: Box::new(propagator),
+            current: Box::new(current),
+        }
+    }
+
+    /// Create a `Propagation` injecting the context that `context` reads
+    /// from the span of the request or redirect hop being sent.
+    ///
+    /// The span is a child of the span current when the request was made.
+    /// This requires the optional `tracing` feature to be enabled.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use reqwest::propagation::{Propagation, SpanContext, TraceContext};
+    ///
+    /// # fn context_of<S>(span: &S) -> Option<(u128, u64)> { None }
+    /// # fn run() -> Result<(), reqwest::Error> {
+    /// let propagation = Propagation::from_span(TraceContext, |span| {
+    ///     let (trace_id, span_id) = context_of(span)?;
+    ///     Some(SpanContext::new(trace_id, span_id))
+    /// });
+    /// let client = reqwest::Client::builder()
+    ///     .propagation(propagation)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn from_span<P, F>(propagator: P, context: F) -> Propagation
+    where
+        P: Propagator,
+        F: Fn(&tracing::Span) -> Option<SpanContext> + Send + Sync + 'static,
+    {
+        Propagation::new(propagator, move || context(&tracing::Span::current()))
+    }
+
+    /// Injects the current context into `headers`, and records the names of
+    /// the headers added in `injected`.
+    ///
+    /// The headers `injected` for a previous hop are removed first.
+    pub(crate) fn inject(&self, headers: &mut HeaderMap, injected: &mut Vec<HeaderName>) {
+        for name in injected.drain(..) {
+            headers.remove(&name);
+        }
+
+        let context = match (self.current)() {
+            Some(context) if context.is_valid() => context,
+            _ => return,
+        };
+        let mut propagated = HeaderMap::new();
+        self.propagator.inject(&context, &mut propagated);
+        // The caller propagates a context of its own.
+        if propagated.keys().any(|name| headers.contains_key(name)) {
+            return;
+        }
+        for (name, value) in propagated.iter() {
+            if !injected.contains(name) {
+                injected.push(name.clone());
+            }
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+impl fmt::Debug for Propagation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Propagation").finish()
+    }
+}
+
+impl SpanContext {
+    /// Create a sampled `SpanContext`.
+    pub fn new(trace_id: u128, span_id: u64) -> SpanContext {
+        SpanContext {
+            trace_id,
+            span_id,
+            sampled: true,
+            trace_state: None,
+        }
+    }
+
+    /// Set whether the trace is sampled.
+    ///
+    /// Default is `true`.
+    pub fn sampled(mut self, sampled: bool) -> SpanContext {
+        self.sampled = sampled;
+        self
+    }
+
+    /// Set the vendor-specific trace state, sent as `tracestate` by the W3C
+    /// propagator.
+    pub fn with_trace_state<S: Into<String>>(mut self, trace_state: S) -> SpanContext {
+        self.trace_state = Some(trace_state.into());
+        self
+    }
+
+    /// The trace ID.
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// The ID of the span the request is sent from.
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// Returns true if the trace is sampled.
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// The vendor-specific trace state, if any.
+    pub fn trace_state(&self) -> Option<&str> {
+        self.trace_state.as_ref().map(|s| &s[..])
+    }
+
+    /// All-zero IDs are invalid in both W3C and B3.
+    fn is_valid(&self) -> bool {
+        self.trace_id != 0 && self.span_id != 0
+    }
+}
+
+impl Propagator for TraceContext {
+    fn inject(&self, context: &SpanContext, headers: &mut HeaderMap) {
+        let traceparent = format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            context.trace_id, context.span_id, context.sampled as u8
+        );
+        insert(headers, "traceparent", &traceparent);
+        match context.trace_state {
+            Some(ref trace_state) if !trace_state.is_empty() => {
+                insert(headers, "tracestate", trace_state);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl B3 {
+    /// Send the context as the `X-B3-TraceId`, `X-B3-SpanId` and
+    /// `X-B3-Sampled` headers.
+    pub fn multiple() -> B3 {
+        B3 { single: false }
+    }
+
+    /// Send the context as a single `b3` header.
+    pub fn single() -> B3 {
+        B3 { single: true }
+    }
+}
+
+impl Propagator for B3 {
+    fn inject(&self, context: &SpanContext, headers: &mut HeaderMap) {
+        let sampled = if context.sampled { "1" } else { "0" };
+        if self.single {
+            let b3 = format!(
+                "{:032x}-{:016x}-{}",
+                context.trace_id, context.span_id, sampled
+            );
+            insert(headers, "b3", &b3);
+        } else {
+            insert(headers, "x-b3-traceid", &format!("{:032x}", context.trace_id));
+            insert(headers, "x-b3-spanid", &format!("{:016x}", context.span_id));
+            insert(headers, "x-b3-sampled", sampled);
+        }
+    }
+}
+
+fn insert(headers: &mut HeaderMap, name: &'static str, value: &str) {
+    match HeaderValue::from_str(value) {
+        Ok(value) => {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+        Err(_) => log::debug!("invalid {} header: {:?}", name, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> SpanContext {
+        SpanContext::new(0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7)
+    }
+
+    #[test]
+    fn test_trace_context() {
+        let mut headers = HeaderMap::new();
+        TraceContext.inject(&context().with_trace_state("congo=t61rcWkgMzE"), &mut headers);
+        assert_eq!(
+            headers["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(headers["tracestate"], "congo=t61rcWkgMzE");
+
+        let mut headers = HeaderMap::new();
+        TraceContext.inject(&context().sampled(false), &mut headers);
+        assert_eq!(
+            headers["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"
+        );
+        assert!(!headers.contains_key("tracestate"));
+    }
+
+    #[test]
+    fn test_b3() {
+        let mut headers = HeaderMap::new();
+        B3::multiple().inject(&context(), &mut headers);
+        assert_eq!(headers["x-b3-traceid"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(headers["x-b3-spanid"], "00f067aa0ba902b7");
+        assert_eq!(headers["x-b3-sampled"], "1");
+
+        let mut headers = HeaderMap::new();
+        B3::single().inject(&context().sampled(false), &mut headers);
+        assert_eq!(
+            headers["b3"],
+            "4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-0"
+        );
+    }
+
+    #[test]
+    fn test_invalid_context_is_not_injected() {
+        let propagation = Propagation::new(TraceContext, || Some(SpanContext::new(0, 1)));
+        let mut headers = HeaderMap::new();
+        propagation.inject(&mut headers, &mut Vec::new());
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn test_reinject_replaces_previous_hop() {
+        let propagation = Propagation::new(TraceContext, || {
+            Some(context().with_trace_state("congo=t61rcWkgMzE"))
+        });
+        let mut headers = HeaderMap::new();
+        let mut injected = Vec::new();
+        propagation.inject(&mut headers, &mut injected);
+        assert_eq!(injected, ["traceparent", "tracestate"]);
+
+        let propagation = Propagation::new(TraceContext, || Some(context().sampled(false)));
+        propagation.inject(&mut headers, &mut injected);
+        assert_eq!(
+            headers["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"
+        );
+        assert!(!headers.contains_key("tracestate"));
+        assert_eq!(injected, ["traceparent"]);
+    }
+
+    #[test]
+    fn test_caller_headers_are_kept() {
+        let propagation = Propagation::new(TraceContext, || Some(context()));
+        let traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let mut headers = HeaderMap::new();
+        headers.insert("traceparent", HeaderValue::from_static(traceparent));
+        headers.insert("tracestate", HeaderValue::from_static("rojo=00f067aa0ba902b7"));
+
+        let mut injected = Vec::new();
+        propagation.inject(&mut headers, &mut injected);
+        assert_eq!(headers["traceparent"], traceparent);
+        assert_eq!(headers["tracestate"], "rojo=00f067aa0ba902b7");
+        assert!(injected.is_empty());
+    }
+}
@@ -0,0 +1,542 @@
+//! Proxy auto-config (PAC) scripts.
+//!
+//! A script is evaluated once, with a small JavaScript engine on a thread of
+//! its own, and the result of its `FindProxyForURL` function is turned into a
+//! list of routes to try.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs, UdpSocket};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use boa_engine::{Context, JsArgs, JsResult, JsString, JsValue, NativeFunction, Source};
+
+use super::{Dst, ProxyScheme};
+use crate::Url;
+
+/// The standard PAC helper functions that don't need anything from the host.
+///
+/// `dnsResolve` and `myIpAddress` are provided natively.
+const HELPERS: &str = r#"
+var wdays = {SUN: 0, MON: 1, TUE: 2, WED: 3, THU: 4, FRI: 5, SAT: 6};
+var months = {JAN: 0, FEB: 1, MAR: 2, APR: 3, MAY: 4, JUN: 5,
+              JUL: 6, AUG: 7, SEP: 8, OCT: 9, NOV: 10, DEC: 11};
+
+function isPlainHostName(host) {
+    return host.indexOf('.') == -1;
+}
+
+function dnsDomainIs(host, domain) {
+    return host.length >= domain.length &&
+        host.substring(host.length - domain.length) == domain;
+}
+
+function localHostOrDomainIs(host, hostdom) {
+    return host == hostdom || hostdom.lastIndexOf(host + '.', 0) == 0;
+}
+
+function isResolvable(host) {
+    return dnsResolve(host) != null;
+}
+
+function dnsDomainLevels(host) {
+    return host.split('.').length - 1;
+}
+
+function convert_addr(ipchars) {
+    var bytes = ipchars.split('.');
+    return ((bytes[0] & 0xff) << 24) | ((bytes[1] & 0xff) << 16) |
+        ((bytes[2] & 0xff) << 8) | (bytes[3] & 0xff);
+}
+
+function isInNet(ipaddr, pattern, maskstr) {
+    if (!/^\d+\.\d+\.\d+\.\d+$/.test(ipaddr)) {
+        ipaddr = dnsResolve(ipaddr);
+        if (ipaddr == null) {
+            return false;
+        }
+    }
+    var mask = convert_addr(maskstr);
+    return (convert_addr(ipaddr) & mask) == (convert_addr(pattern) & mask);
+}
+
+function shExpMatch(str, shexp) {
+    var pattern = shexp
+        .replace(/[.+^${}()|[\]\\]/g, '\\$&')
+        .replace(/\*/g, '.*')
+        .replace(/\?/g, '.');
+    return new RegExp('^' + pattern + '$').test(str);
+}
+
+function inRange(start, value, end) {
+    if (start <= end) {
+        return start <= value && value <= end;
+    }
+    return value >= start || value <= end;
+}
+
+function pacArgs(args) {
+    args = Array.prototype.slice.call(args);
+    var gmt = args.length > 0 && args[args.length - 1] == 'GMT';
+    if (gmt) {
+        args.pop();
+    }
+    return {args: args, gmt: gmt, now: new Date()};
+}
+
+function weekdayRange() {
+    var a = pacArgs(arguments);
+    if (a.args.length < 1 || a.args.length > 2) {
+        return false;
+    }
+    var start = wdays[a.args[0]];
+    var end = a.args.length == 2 ? wdays[a.args[1]] : start;
+    if (start === undefined || end === undefined) {
+        return false;
+    }
+    return inRange(start, a.gmt ? a.now.getUTCDay() : a.now.getDay(), end);
+}
+
+function dateRange() {
+    var a = pacArgs(arguments);
+    var len = a.args.length;
+    if (len < 1 || len > 6 || (len > 1 && len % 2)) {
+        return false;
+    }
+    var today = a.gmt
+        ? [a.now.getUTCFullYear(), a.now.getUTCMonth(), a.now.getUTCDate()]
+        : [a.now.getFullYear(), a.now.getMonth(), a.now.getDate()];
+    function parse(parts) {
+        var date = [null, null, null];
+        for (var i = 0; i < parts.length; i++) {
+            if (parts[i] in months) {
+                date[1] = months[parts[i]];
+            } else if (Number(parts[i]) < 32) {
+                date[2] = Number(parts[i]);
+            } else {
+                date[0] = Number(parts[i]);
+            }
+        }
+        return date;
+    }
+    var half = Math.max(len / 2, 1);
+    var start = parse(a.args.slice(0, half));
+    var end = parse(a.args.slice(len - half));
+    // Only compare the fields that were given.
+    function key(date) {
+        var k = 0;
+        for (var i = 0; i < 3; i++) {
+            k = k * 32 + (start[i] == null ? 0 : date[i]);
+        }
+        return k;
+    }
+    return inRange(key(start), key(today), key(end));
+}
+
+function timeRange() {
+    var a = pacArgs(arguments);
+    var len = a.args.length;
+    var now = a.gmt
+        ? [a.now.getUTCHours(), a.now.getUTCMinutes(), a.now.getUTCSeconds()]
+        : [a.now.getHours(), a.now.getMinutes(), a.now.getSeconds()];
+    if (len == 1) {
+        return now[0] == Number(a.args[0]);
+    }
+    if (len != 2 && len != 4 && len != 6) {
+        return false;
+    }
+    var half = len / 2;
+    function key(time) {
+        var k = 0;
+        for (var i = 0; i < half; i++) {
+            k = k * 60 + Number(time[i]);
+        }
+        return k;
+    }
+    return inRange(key(a.args.slice(0, half)), key(now), key(a.args.slice(half)));
+}
+"#;
+
+/// How long the routes found for a URL are reused.
+///
+/// A request asks for its routes several times, from the client and from
+/// the connector, and this keeps that to a single run of the script.
+const MEMO_TTL: Duration = Duration::from_secs(2);
+
+/// The most URLs to remember routes for.
+const MEMO_MAX: usize = 256;
+
+type Job = (String, String, mpsc::Sender<Result<String, String>>);
+
+type Memo = HashMap<String, (Instant, Vec<Option<ProxyScheme>>)>;
+
+/// A parsed proxy auto-config script.
+///
+/// A JavaScript context can't leave the thread it was made on, so the
+/// evaluated script lives on a thread of its own, which exits when the
+/// `Pac` is dropped. Calls block on that thread, including on the DNS
+/// lookups of `dnsResolve` and `isInNet`, so async callers go through
+/// `proxy::resolve_pac` first.
+pub(crate) struct Pac {
+    jobs: Mutex<mpsc::Sender<Job>>,
+    memo: Mutex<Memo>,
+}
+
+impl Pac {
+    pub(crate) fn new(script: String) -> crate::Result<Pac> {
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let (ready_tx, ready) = mpsc::channel();
+        thread::Builder::new()
+            .name("reqwest-pac".into())
+            .spawn(move || {
+                let mut context = match compile(&script) {
+                    Ok(context) => {
+                        let _ = ready_tx.send(Ok(()));
+                        context
+                    }
+                    Err(err) => {
+                        let _ = ready_tx.send(Err(err));
+                        return;
+                    }
+                };
+                for (url, host, reply) in rx {
+                    let _ = reply.send(call(&mut context, url, host));
+                }
+            })
+            .map_err(crate::error::builder)?;
+        ready
+            .recv()
+            .unwrap_or_else(|_| Err("PAC thread exited".to_string()))
+            .map_err(crate::error::builder)?;
+
+        Ok(Pac {
+            jobs: Mutex::new(jobs),
+            memo: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Every route the script returns for `uri`, in order, where `None`
+    /// means connecting directly.
+    ///
+    /// If the script fails, the connection is made directly.
+    pub(crate) fn find_proxy<D: Dst>(&self, uri: &D) -> Vec<Option<ProxyScheme>> {
+        let url = url_of(uri);
+        if let Some(routes) = self.memoized(&url) {
+            return routes;
+        }
+
+        let routes = match self.call(url.clone(), uri.host().to_owned()) {
+            Ok(result) => {
+                let routes = parse_routes(&result);
+                if routes.is_empty() {
+                    vec![None]
+                } else {
+                    routes
+                }
+            }
+            Err(err) => {
+                log::warn!("PAC script failed, connecting directly: {}", err);
+                vec![None]
+            }
+        };
+
+        let mut memo = self.memo.lock().unwrap();
+        memo.retain(|_, (found, _)| found.elapsed() < MEMO_TTL);
+        if memo.len() >= MEMO_MAX {
+            memo.clear();
+        }
+        memo.insert(url, (Instant::now(), routes.clone()));
+        routes
+    }
+
+    /// Whether `find_proxy` can answer for `uri` without running the script.
+    pub(crate) fn is_resolved<D: Dst>(&self, uri: &D) -> bool {
+        self.memoized(&url_of(uri)).is_some()
+    }
+
+    fn memoized(&self, url: &str) -> Option<Vec<Option<ProxyScheme>>> {
+        match self.memo.lock().unwrap().get(url) {
+            Some((found, routes)) if found.elapsed() < MEMO_TTL => Some(routes.clone()),
+            _ => None,
+        }
+    }
+
+    fn call(&self, url: String, host: String) -> Result<String, String> {
+        let (reply, result) = mpsc::channel();
+        self.jobs
+            .lock()
+            .unwrap()
+            .send((url, host, reply))
+            .map_err(|_| "PAC thread exited".to_string())?;
+        result
+            .recv()
+            .unwrap_or_else(|_| Err("PAC thread exited".to_string()))
+    }
+}
+
+/// The URL passed to `FindProxyForURL`, which is also the memo key.
+///
+/// The path and query of `https` URLs are stripped, as browsers do, but a
+/// plain `http` URL is given whole, so the script can route by path.
+fn url_of<D: Dst>(uri: &D) -> String {
+    let path = if uri.scheme() == "http" {
+        uri.path_and_query()
+    } else {
+        "/"
+    };
+    match uri.port() {
+        Some(port) => format!("{}://{}:{}{}", uri.scheme(), uri.host(), port, path),
+        None => format!("{}://{}{}", uri.scheme(), uri.host(), path),
+    }
+}
+
+/// A context with the helpers and the script evaluated.
+fn compile(script: &str) -> Result<Context, String> {
+    let mut context = Context::default();
+    let natives = vec![
+        ("dnsResolve", 1, NativeFunction::from_fn_ptr(dns_resolve)),
+        ("myIpAddress", 0, NativeFunction::from_fn_ptr(my_ip_address)),
+    ];
+    for (name, length, function) in natives {
+        context
+            .register_global_builtin_callable(JsString::from(name), length, function)
+            .map_err(|err| err.to_string())?;
+    }
+    for source in &[HELPERS, script] {
+        context
+            .eval(Source::from_bytes(source.as_bytes()))
+            .map_err(|err| err.to_string())?;
+    }
+    let find_proxy = context
+        .global_object()
+        .get(JsString::from("FindProxyForURL"), &mut context)
+        .map_err(|err| err.to_string())?;
+    if find_proxy.as_callable().is_none() {
+        return Err("PAC script does not define FindProxyForURL".to_string());
+    }
+    Ok(context)
+}
+
+fn call(context: &mut Context, url: String, host: String) -> Result<String, String> {
+    let find_proxy = context
+        .global_object()
+        .get(JsString::from("FindProxyForURL"), context)
+        .map_err(|err| err.to_string())?;
+    let find_proxy = find_proxy
+        .as_callable()
+        .ok_or_else(|| "FindProxyForURL is not a function".to_string())?;
+    let args = [
+        JsValue::from(JsString::from(url)),
+        JsValue::from(JsString::from(host)),
+    ];
+    let result = find_proxy
+        .call(&JsValue::undefined(), &args, context)
+        .and_then(|result| result.to_string(context))
+        .map_err(|err| err.to_string())?;
+    Ok(result.to_std_string_escaped())
+}
+
+impl fmt::Debug for Pac {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Pac { .. }")
+    }
+}
+
+/// Parse a `FindProxyForURL` result, such as `"PROXY a:8080; DIRECT"`.
+///
+/// Entries that aren't understood are skipped.
+fn parse_routes(result: &str) -> Vec<Option<ProxyScheme>> {
+    let mut routes = Vec::new();
+    for entry in result.split(';') {
+        let mut parts = entry.split_whitespace();
+        let kind = match parts.next() {
+            Some(kind) => kind.to_ascii_uppercase(),
+            None => continue,
+        };
+        let scheme = match kind.as_str() {
+            "DIRECT" => {
+                routes.push(None);
+                continue;
+            }
+            "PROXY" | "HTTP" => "http",
+            "HTTPS" => "https",
+            // `SOCKS` means SOCKS4, which isn't supported.
+            "SOCKS5" => "socks5",
+            _ => {
+                log::debug!("ignoring unknown PAC route {:?}", entry);
+                continue;
+            }
+        };
+        let parsed = parts
+            .next()
+            .ok_or_else(|| crate::error::builder("missing proxy address"))
+            .and_then(|addr| {
+                Url::parse(&format!("{}://{}", scheme, addr)).map_err(crate::error::builder)
+            })
+            .and_then(ProxyScheme::parse);
+        match parsed {
+            Ok(proxy_scheme) => routes.push(Some(proxy_scheme)),
+            Err(err) => log::debug!("ignoring PAC route {:?}: {}", entry, err),
+        }
+    }
+    routes
+}
+
+fn resolve_ipv4(host: &str) -> Option<Ipv4Addr> {
+    (host, 0)
+        .to_socket_addrs()
+        .ok()?
+        .filter_map(|addr| match addr.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .next()
+}
+
+fn dns_resolve(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let host = args.get_or_undefined(0).to_string(context)?;
+    Ok(match resolve_ipv4(&host.to_std_string_escaped()) {
+        Some(ip) => JsValue::from(JsString::from(ip.to_string())),
+        None => JsValue::null(),
+    })
+}
+
+fn my_ip_address(_this: &JsValue, _args: &[JsValue], _context: &mut Context) -> JsResult<JsValue> {
+    // Connecting a UDP socket sends nothing, but picks the local address
+    // that would be used to reach the outside world.
+    let ip = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(8, 8, 8, 8), 53))?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .unwrap_or_else(|_| IpAddr::V4(Ipv4Addr::LOCALHOST));
+    Ok(JsValue::from(JsString::from(ip.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(pac: &Pac, host: &str) -> Vec<Option<ProxyScheme>> {
+        let uri = format!("http://{}/", host).parse::<http::Uri>().unwrap();
+        pac.find_proxy(&uri)
+    }
+
+    fn hosts(routes: &[Option<ProxyScheme>]) -> Vec<String> {
+        routes
+            .iter()
+            .map(|route| match route {
+                Some(scheme) => scheme.to_url().to_string(),
+                None => "DIRECT".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_routes() {
+        let routes = parse_routes("PROXY a.prox:8080; HTTPS b.prox:443;DIRECT; BOGUS c:1; PROXY");
+        assert_eq!(
+            hosts(&routes),
+            ["http://a.prox:8080/", "https://b.prox/", "DIRECT"]
+        );
+        assert!(parse_routes("").is_empty());
+        // `SOCKS` is SOCKS4
+        assert_eq!(hosts(&parse_routes("SOCKS a.prox:1080; DIRECT")), ["DIRECT"]);
+    }
+
+    #[test]
+    fn test_invalid_script() {
+        assert!(Pac::new("function (".into()).is_err());
+        assert!(Pac::new("var x = 1;".into()).is_err());
+    }
+
+    #[test]
+    fn test_find_proxy() {
+        let pac = Pac::new(
+            r#"
+            function FindProxyForURL(url, host) {
+                if (isPlainHostName(host) || dnsDomainIs(host, ".local.example")) {
+                    return "DIRECT";
+                }
+                if (shExpMatch(url, "http://*.test.example/*")) {
+                    return "PROXY test.prox:3128";
+                }
+                if (isInNet(host, "10.0.0.0", "255.0.0.0")) {
+                    return "PROXY ten.prox:3128; DIRECT";
+                }
+                return "PROXY a.prox:8080; PROXY b.prox:8080";
+            }
+            "#
+            .into(),
+        )
+        .unwrap();
+
+        assert_eq!(hosts(&eval(&pac, "intranet")), ["DIRECT"]);
+        assert_eq!(hosts(&eval(&pac, "www.local.example")), ["DIRECT"]);
+        assert_eq!(
+            hosts(&eval(&pac, "www.test.example")),
+            ["http://test.prox:3128/"]
+        );
+        assert_eq!(
+            hosts(&eval(&pac, "10.1.2.3")),
+            ["http://ten.prox:3128/", "DIRECT"]
+        );
+        assert_eq!(
+            hosts(&eval(&pac, "192.168.1.1")),
+            ["http://a.prox:8080/", "http://b.prox:8080/"]
+        );
+    }
+
+    #[test]
+    fn test_find_proxy_by_path() {
+        let pac = Pac::new(
+            r#"
+            function FindProxyForURL(url, host) {
+                if (shExpMatch(url, "*/api/*")) {
+                    return "PROXY api.prox:8080";
+                }
+                return "DIRECT";
+            }
+            "#
+            .into(),
+        )
+        .unwrap();
+
+        let find = |url: &str| hosts(&pac.find_proxy(&url.parse::<http::Uri>().unwrap()));
+        assert_eq!(find("http://a.example/api/v1?q=1"), ["http://api.prox:8080/"]);
+        assert_eq!(find("http://a.example/www/"), ["DIRECT"]);
+        // the path of an https URL isn't given to the script
+        assert_eq!(find("https://a.example/api/v1"), ["DIRECT"]);
+    }
+
+    #[test]
+    fn test_script_evaluated_once() {
+        let pac = Pac::new(
+            r#"
+            var calls = 0;
+            function FindProxyForURL(url, host) {
+                calls++;
+                return "PROXY p" + calls + ".prox:8080";
+            }
+            "#
+            .into(),
+        )
+        .unwrap();
+
+        // the same URL is memoized, and the script keeps its state
+        assert_eq!(hosts(&eval(&pac, "a.example")), ["http://p1.prox:8080/"]);
+        assert_eq!(hosts(&eval(&pac, "a.example")), ["http://p1.prox:8080/"]);
+        assert_eq!(hosts(&eval(&pac, "b.example")), ["http://p2.prox:8080/"]);
+        assert!(pac.is_resolved(&"http://b.example/".parse::<http::Uri>().unwrap()));
+        assert!(!pac.is_resolved(&"http://c.example/".parse::<http::Uri>().unwrap()));
+    }
+
+    #[test]
+    fn test_script_error_is_direct() {
+        let pac = Pac::new("function FindProxyForURL(url, host) { throw 'oops'; }".into()).unwrap();
+        assert_eq!(hosts(&eval(&pac, "example.com")), ["DIRECT"]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+/// A PROXY protocol header sent on every new connection.
+///
+/// The [PROXY protocol][spec] lets a TCP load balancer or proxy in front of a
+/// service learn the address of the original client. When configured, the
+/// header is written right after the TCP connection is established, before
+/// any TLS handshake or HTTP traffic.
+///
+/// # Example
+///
+/// ```
+/// use std::net::SocketAddr;
+///
+/// # fn run() -> Result<(), reqwest::Error> {
+/// let client_addr: SocketAddr = "203.0.113.7:51234".parse().unwrap();
+///
+/// let client = reqwest::Client::builder()
+///     .proxy_protocol(reqwest::ProxyProtocol::v2(client_addr))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [spec]: https://www.haproxy.org/download/2.2/doc/proxy-protocol.txt
+#[derive(Clone)]
+pub struct ProxyProtocol {
+    version: Version,
+    source: SocketAddr,
+    destination: Option<SocketAddr>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Version {
+    V1,
+    V2,
+}
+
+/// The v2 binary header signature.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+impl ProxyProtocol {
+    /// A human-readable version 1 header, announcing `source` as the client.
+    pub fn v1(source: SocketAddr) -> ProxyProtocol {
+        ProxyProtocol::new(Version::V1, source)
+    }
+
+    /// A binary version 2 header, announcing `source` as the client.
+    pub fn v2(source: SocketAddr) -> ProxyProtocol {
+        ProxyProtocol::new(Version::V2, source)
+    }
+
+    fn new(version: Version, source: SocketAddr) -> ProxyProtocol {
+        ProxyProtocol {
+            version,
+            source,
+            destination: None,
+        }
+    }
+
+    /// Set the destination address announced in the header.
+    ///
+    /// By default, the address of the remote end of the connection is used.
+    pub fn destination(mut self, addr: SocketAddr) -> ProxyProtocol {
+        self.destination = Some(addr);
+        self
+    }
+
+    /// Encode the header for a connection to `peer`.
+    pub(crate) fn encode(&self, peer: SocketAddr) -> Vec<u8> {
+        let (source, destination) = same_family(self.source, self.destination.unwrap_or(peer));
+        match self.version {
+            Version::V1 => {
+                let proto = if source.is_ipv4() { "TCP4" } else { "TCP6" };
+                format!(
+                    "PROXY {} {} {} {} {}\r\n",
+                    proto,
+                    source.ip(),
+                    destination.ip(),
+                    source.port(),
+                    destination.port()
+                )
+                .into_bytes()
+            }
+            Version::V2 => {
+                let mut buf = V2_SIGNATURE.to_vec();
+                // version 2, PROXY command
+                buf.push(0x21);
+                match (source.ip(), destination.ip()) {
+                    (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                        // TCP over IPv4
+                        buf.push(0x11);
+                        buf.extend_from_slice(&12u16.to_be_bytes());
+                        buf.extend_from_slice(&src.octets());
+                        buf.extend_from_slice(&dst.octets());
+                    }
+                    (src, dst) => {
+                        // TCP over IPv6
+                        buf.push(0x21);
+                        buf.extend_from_slice(&36u16.to_be_bytes());
+                        buf.extend_from_slice(&to_ipv6(src).octets());
+                        buf.extend_from_slice(&to_ipv6(dst).octets());
+                    }
+                }
+                buf.extend_from_slice(&source.port().to_be_bytes());
+                buf.extend_from_slice(&destination.port().to_be_bytes());
+                buf
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ProxyProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut builder = f.debug_struct("ProxyProtocol");
+        builder.field("version", &self.version);
+        builder.field("source", &self.source);
+        if let Some(ref destination) = self.destination {
+            builder.field("destination", destination);
+        }
+        builder.finish()
+    }
+}
+
+/// Both addresses need to be of the same family, so mix IPv4 with IPv6 by
+/// using IPv4-mapped addresses.
+fn same_family(source: SocketAddr, destination: SocketAddr) -> (SocketAddr, SocketAddr) {
+    if source.is_ipv4() == destination.is_ipv4() {
+        (source, destination)
+    } else {
+        (
+            SocketAddr::new(IpAddr::V6(to_ipv6(source.ip())), source.port()),
+            SocketAddr::new(IpAddr::V6(to_ipv6(destination.ip())), destination.port()),
+        )
+    }
+}
+
+fn to_ipv6(ip: IpAddr) -> std::net::Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_v1_ipv4() {
+        let header = ProxyProtocol::v1(addr("192.0.2.1:4000")).encode(addr("198.51.100.2:443"));
+        assert_eq!(header, b"PROXY TCP4 192.0.2.1 198.51.100.2 4000 443\r\n");
+    }
+
+    #[test]
+    fn test_v1_mixed_families() {
+        let header = ProxyProtocol::v1(addr("192.0.2.1:4000"))
+            .destination(addr("[2001:db8::1]:80"))
+            .encode(addr("198.51.100.2:443"));
+        assert_eq!(
+            header,
+            &b"PROXY TCP6 ::ffff:192.0.2.1 2001:db8::1 4000 80\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn test_v2_ipv4() {
+        let header = ProxyProtocol::v2(addr("192.0.2.1:4000"))
+            .destination(addr("198.51.100.2:80"))
+            .encode(addr("127.0.0.1:443"));
+
+        let mut expected = V2_SIGNATURE.to_vec();
+        expected.extend_from_slice(&[0x21, 0x11, 0, 12]);
+        expected.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 2]);
+        expected.extend_from_slice(&[0x0f, 0xa0, 0, 80]);
+        assert_eq!(header, expected);
+    }
+
+    #[test]
+    fn test_v2_ipv6() {
+        let header = ProxyProtocol::v2(addr("[2001:db8::1]:4000")).encode(addr("[::1]:443"));
+        assert_eq!(&header[12..16], &[0x21, 0x21, 0, 36]);
+        assert_eq!(header.len(), 16 + 36);
+        assert_eq!(&header[48..], &[0x0f, 0xa0, 0x01, 0xbb]);
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long each phase of a request took.
+///
+/// The connection phases (`dns`, `connect` and `tls`) are only available
+/// when the request made a new connection. When a pooled connection was
+/// reused, they are `None` and `is_reused` returns `true`.
+///
+/// Every phase is measured separately, so adding them up gives roughly the
+/// total time of the request.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timings {
+    pub(crate) dns: Option<Duration>,
+    pub(crate) connect: Option<Duration>,
+    pub(crate) tls: Option<Duration>,
+    pub(crate) request_sent: Option<Duration>,
+    pub(crate) first_byte: Option<Duration>,
+    pub(crate) body_complete: Option<Duration>,
+    pub(crate) reused: bool,
+}
+
+impl Timings {
+    /// Time spent resolving the host name.
+    ///
+    /// `None` if no lookup was needed, such as for IP addresses, or when the
+    /// connection was reused.
+    pub fn dns(&self) -> Option<Duration> {
+        self.dns
+    }
+
+    /// Time spent establishing the TCP connection.
+    ///
+    /// This includes setting up a tunnel or SOCKS session when a proxy is
+    /// used.
+    pub fn connect(&self) -> Option<Duration> {
+        self.connect
+    }
+
+    /// Time spent on TLS handshakes.
+    pub fn tls(&self) -> Option<Duration> {
+        self.tls
+    }
+
+    /// Time spent sending the request, once the connection was ready.
+    pub fn request_sent(&self) -> Option<Duration> {
+        self.request_sent
+    }
+
+    /// Time spent waiting for the response, from the request being sent to
+    /// its headers being received.
+    pub fn first_byte(&self) -> Option<Duration> {
+        self.first_byte
+    }
+
+    /// Time spent receiving the body, after the headers.
+    ///
+    /// `None` until the body has been read to the end.
+    pub fn body_complete(&self) -> Option<Duration> {
+        self.body_complete
+    }
+
+    /// Returns true if the request was sent on a pooled connection.
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+}
+
+/// The timings of a response, shared with its body which completes them.
+#[derive(Clone)]
+pub(crate) struct TimingsHandle {
+    timings: Arc<Mutex<Timings>>,
+    headers_received: Instant,
+}
+
+impl TimingsHandle {
+    pub(crate) fn new(timings: Timings, headers_received: Instant) -> TimingsHandle {
+        TimingsHandle {
+            timings: Arc::new(Mutex::new(timings)),
+            headers_received,
+        }
+    }
+
+    pub(crate) fn get(&self) -> Timings {
+        *self.timings.lock().unwrap()
+    }
+
+    pub(crate) fn body_complete(&self) {
+        let mut timings = self.timings.lock().unwrap();
+        if timings.body_complete.is_none() {
+            timings.body_complete = Some(self.headers_received.elapsed());
+        }
+    }
+}
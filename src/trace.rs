@@ -0,0 +1,61 @@
+//! Spans for the `tracing` feature.
+//!
+//! Call sites use these helpers instead of `tracing` directly, so that they
+//! compile to nothing when the feature is disabled.
+
+use std::fmt;
+use std::future::Future;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+/// Creates a debug-level span, or a no-op one without the `tracing` feature.
+macro_rules! span {
+    ($name:expr) => {
+        span!($name,)
+    };
+    ($name:expr, $($fields:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!($name, $($fields)*);
+        #[cfg(not(feature = "tracing"))]
+        let span = $crate::trace::Span;
+        span
+    }};
+}
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Debug)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    #[cfg(feature = "blocking")]
+    pub(crate) fn current() -> Span {
+        Span
+    }
+
+    pub(crate) fn entered(self) -> Entered {
+        Entered
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Entered;
+
+/// Records a field declared as empty when the span was created.
+pub(crate) fn record<V: fmt::Display>(_span: &Span, _field: &'static str, _value: V) {
+    #[cfg(feature = "tracing")]
+    _span.record(_field, tracing::field::display(_value));
+}
+
+/// Runs `fut` inside `span`.
+#[cfg(feature = "tracing")]
+pub(crate) fn instrument<F: Future>(fut: F, span: Span) -> impl Future<Output = F::Output> {
+    tracing::Instrument::instrument(fut, span)
+}
+
+/// Runs `fut` inside `span`.
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn instrument<F: Future>(fut: F, _span: Span) -> impl Future<Output = F::Output> {
+    fut
+}
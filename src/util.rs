@@ -63,3 +63,41 @@ pub(crate) fn replace_headers(dst: &mut HeaderMap, src: HeaderMap) {
         }
     }
 }
+
+/// A timeout that restarts every time some progress is made.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct IdleTimeout {
+    duration: Option<std::time::Duration>,
+    delay: Option<tokio::time::Delay>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl IdleTimeout {
+    pub(crate) fn new(duration: Option<std::time::Duration>) -> IdleTimeout {
+        IdleTimeout {
+            duration,
+            delay: None,
+        }
+    }
+
+    /// Polls the timeout after the guarded operation returned `Pending`.
+    ///
+    /// The timer starts on the first call after a `reset`.
+    pub(crate) fn poll_elapsed(&mut self, cx: &mut std::task::Context) -> std::task::Poll<()> {
+        use std::future::Future;
+
+        let duration = match self.duration {
+            Some(duration) => duration,
+            None => return std::task::Poll::Pending,
+        };
+        let delay = self
+            .delay
+            .get_or_insert_with(|| tokio::time::delay_for(duration));
+        std::pin::Pin::new(delay).poll(cx)
+    }
+
+    /// Restarts the timeout, after the guarded operation made progress.
+    pub(crate) fn reset(&mut self) {
+        self.delay = None;
+    }
+}
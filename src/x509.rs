@@ -0,0 +1,231 @@
+//! Just enough DER parsing of X.509 certificates for pinning and for the
+//! `Certificate` accessors.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const OID: u8 = 0x06;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const VERSION: u8 = 0xa0;
+
+/// The parts of a certificate's TBSCertificate that reqwest looks at.
+pub(crate) struct Fields<'a> {
+    /// The contents of the issuer Name.
+    pub(crate) issuer: &'a [u8],
+    /// The contents of the Validity.
+    pub(crate) validity: &'a [u8],
+    /// The contents of the subject Name.
+    pub(crate) subject: &'a [u8],
+    /// The whole SubjectPublicKeyInfo, including its header.
+    pub(crate) spki: &'a [u8],
+}
+
+/// Parses a DER X.509 certificate, up to the SubjectPublicKeyInfo.
+pub(crate) fn fields(cert: &[u8]) -> Option<Fields<'_>> {
+    let (cert, _) = element(cert, SEQUENCE)?;
+    let (tbs, _) = element(cert, SEQUENCE)?;
+
+    // Skip the optional version, then the serial number and signature
+    // algorithm.
+    let mut rest = tbs;
+    if rest.first() == Some(&VERSION) {
+        rest = element(rest, VERSION)?.1;
+    }
+    rest = element(rest, INTEGER)?.1;
+    rest = element(rest, SEQUENCE)?.1;
+
+    let (issuer, rest) = element(rest, SEQUENCE)?;
+    let (validity, rest) = element(rest, SEQUENCE)?;
+    let (subject, rest) = element(rest, SEQUENCE)?;
+    let (_, after) = element(rest, SEQUENCE)?;
+    Some(Fields {
+        issuer,
+        validity,
+        subject,
+        spki: &rest[..rest.len() - after.len()],
+    })
+}
+
+/// Formats the contents of a Name as comma separated attributes, in
+/// certificate order, e.g. `CN=example.com, O=Example`.
+///
+/// Well known attribute types are abbreviated, others use their dotted OID.
+pub(crate) fn name(mut der: &[u8]) -> Option<String> {
+    let mut attrs = Vec::new();
+    while !der.is_empty() {
+        let (mut rdn, rest) = element(der, SET)?;
+        der = rest;
+        while !rdn.is_empty() {
+            let (attr, rest) = element(rdn, SEQUENCE)?;
+            rdn = rest;
+            let (oid, value) = element(attr, OID)?;
+            let (_, value, _) = any_element(value)?;
+            attrs.push(format!(
+                "{}={}",
+                attribute_type(oid),
+                String::from_utf8_lossy(value)
+            ));
+        }
+    }
+    Some(attrs.join(", "))
+}
+
+/// Returns the not-before and not-after times of the contents of a
+/// Validity.
+pub(crate) fn validity(der: &[u8]) -> Option<(SystemTime, SystemTime)> {
+    let (tag, not_before, rest) = any_element(der)?;
+    let not_before = time(tag, not_before)?;
+    let (tag, not_after, _) = any_element(rest)?;
+    let not_after = time(tag, not_after)?;
+    Some((not_before, not_after))
+}
+
+fn attribute_type(oid: &[u8]) -> String {
+    match oid {
+        [0x55, 0x04, 0x03] => "CN".into(),
+        [0x55, 0x04, 0x05] => "SERIALNUMBER".into(),
+        [0x55, 0x04, 0x06] => "C".into(),
+        [0x55, 0x04, 0x07] => "L".into(),
+        [0x55, 0x04, 0x08] => "ST".into(),
+        [0x55, 0x04, 0x09] => "STREET".into(),
+        [0x55, 0x04, 0x0a] => "O".into(),
+        [0x55, 0x04, 0x0b] => "OU".into(),
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19] => "DC".into(),
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress".into(),
+        _ => dotted(oid),
+    }
+}
+
+fn dotted(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for &byte in oid {
+        arc = arc << 7 | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        }
+    }
+    arcs.iter()
+        .map(|arc| arc.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Parses a UTCTime or GeneralizedTime in the `Z` form that RFC 5280
+/// requires.
+fn time(tag: u8, der: &[u8]) -> Option<SystemTime> {
+    let text = std::str::from_utf8(der).ok()?;
+    if !text.ends_with('Z') || !text[..text.len() - 1].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (year, rest) = match (tag, text.len()) {
+        (UTC_TIME, 13) => {
+            // Two digit years are 1950 to 2049.
+            let year: i64 = text[..2].parse().ok()?;
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &text[2..],
+            )
+        }
+        (GENERALIZED_TIME, 15) => (text[..4].parse().ok()?, &text[4..]),
+        _ => return None,
+    };
+    let field = |i: usize| rest[i..i + 2].parse::<i64>().ok();
+    let (month, day) = (field(0)?, field(2)?);
+    let (hour, minute, second) = (field(4)?, field(6)?, field(8)?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.wrapping_neg() as u64))
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Splits the DER element with `tag` off the front of `der`, returning its
+/// contents and the bytes after it.
+pub(crate) fn element(der: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    match any_element(der)? {
+        (found, contents, rest) if found == tag => Some((contents, rest)),
+        _ => None,
+    }
+}
+
+/// Splits any DER element off the front of `der`, returning its tag,
+/// contents and the bytes after it.
+fn any_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *der.first()?;
+    let (len, header) = match *der.get(1)? {
+        len @ 0..=0x7f => (len as usize, 2),
+        long @ 0x81..=0x84 => {
+            let octets = (long & 0x7f) as usize;
+            let len = der
+                .get(2..2 + octets)?
+                .iter()
+                .fold(0, |len, &octet| len << 8 | octet as usize);
+            (len, 2 + octets)
+        }
+        _ => return None,
+    };
+    let contents = der.get(header..header.checked_add(len)?)?;
+    Some((tag, contents, &der[header + len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_attributes() {
+        // CN=example.com, O=Example, 2.5.4.99=x
+        let der = b"\x31\x14\x30\x12\x06\x03\x55\x04\x03\x0c\x0bexample.com\
+            \x31\x10\x30\x0e\x06\x03\x55\x04\x0a\x0c\x07Example\
+            \x31\x0a\x30\x08\x06\x03\x55\x04\x63\x0c\x01x";
+        assert_eq!(name(der).unwrap(), "CN=example.com, O=Example, 2.5.4.99=x");
+        assert!(name(b"\x31\x05\x30").is_none());
+    }
+
+    #[test]
+    fn validity_times() {
+        let der = b"\x17\x0d491231235959Z\x18\x0f20500101000000Z";
+        let (not_before, not_after) = validity(der).unwrap();
+        assert_eq!(not_before, UNIX_EPOCH + Duration::from_secs(2_524_607_999));
+        assert_eq!(not_after, UNIX_EPOCH + Duration::from_secs(2_524_608_000));
+
+        let der = b"\x17\x0d691231235959Z\x17\x0d700101000000Z";
+        let (not_before, not_after) = validity(der).unwrap();
+        assert_eq!(not_before, UNIX_EPOCH - Duration::from_secs(1));
+        assert_eq!(not_after, UNIX_EPOCH);
+
+        assert!(validity(b"\x17\x0b4912312359Z\x17\x0b4912312359Z").is_none());
+        assert!(validity(b"\x17\x0d491331235959Z\x17\x0d491231235959Z").is_none());
+    }
+}
@@ -288,3 +288,48 @@ fn test_blocking_inside_a_runtime() {
         let _should_panic = reqwest::blocking::get(&url);
     });
 }
+
+#[test]
+fn test_pool_stats_and_shutdown() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let client = reqwest::blocking::Client::new();
+    let url = format!("http://{}/pool", server.addr());
+
+    client.get(&url).send().unwrap().text().unwrap();
+    let stats = client
+        .pool_stats()
+        .host(&format!("http://{}", server.addr()))
+        .expect("host stats");
+    assert_eq!((stats.idle(), stats.active()), (1, 0));
+
+    client.shutdown();
+    let err = client.get(&url).send().unwrap_err();
+    assert!(err.is_request());
+}
+
+#[test]
+fn test_shutdown_timeout() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let client = reqwest::blocking::Client::new();
+    let url = format!("http://{}/pool", server.addr());
+
+    let res = client.get(&url).send().unwrap();
+    assert!(!client.shutdown_timeout(std::time::Duration::from_millis(50)));
+
+    drop(res);
+    assert!(client.shutdown_timeout(std::time::Duration::from_secs(5)));
+}
+
+#[test]
+fn test_preconnect() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let client = reqwest::blocking::Client::new();
+    let host = format!("http://{}", server.addr());
+
+    client.preconnect_n(&host, 2).unwrap();
+    let stats = client.pool_stats().host(&host).expect("host stats");
+    assert_eq!((stats.idle(), stats.active()), (2, 0));
+}
@@ -191,3 +191,497 @@ fn use_preconfigured_rustls_default() {
         .build()
         .expect("preconfigured rustls tls");
 }
+
+#[cfg(feature = "__tls")]
+#[test]
+fn tls_built_in_root_certs_disabled() {
+    let builder = reqwest::Client::builder().tls_built_in_root_certs(false);
+    assert!(format!("{:?}", builder).contains("tls_built_in_root_certs: false"));
+
+    builder.build().expect("client without built-in roots");
+}
+
+#[cfg(feature = "native-tls")]
+#[test]
+fn native_tls_version_bounds() {
+    use reqwest::TlsVersion;
+
+    reqwest::Client::builder()
+        .use_native_tls()
+        .min_tls_version(TlsVersion::Tls1_2)
+        .max_tls_version(TlsVersion::Tls1_3)
+        .build()
+        .expect("native-tls with TLS 1.2 or higher");
+
+    reqwest::Client::builder()
+        .use_native_tls()
+        .min_tls_version(TlsVersion::Tls1_3)
+        .build()
+        .expect_err("native-tls cannot require TLS 1.3");
+}
+
+#[cfg(feature = "rustls-tls")]
+#[test]
+fn rustls_tls_version_bounds() {
+    use reqwest::TlsVersion;
+
+    reqwest::Client::builder()
+        .use_rustls_tls()
+        .min_tls_version(TlsVersion::Tls1_3)
+        .build()
+        .expect("rustls with TLS 1.3 only");
+
+    reqwest::Client::builder()
+        .use_rustls_tls()
+        .max_tls_version(TlsVersion::Tls1_1)
+        .build()
+        .expect_err("rustls doesn't support TLS 1.1");
+
+    reqwest::Client::builder()
+        .use_rustls_tls()
+        .min_tls_version(TlsVersion::Tls1_3)
+        .max_tls_version(TlsVersion::Tls1_2)
+        .build()
+        .expect_err("empty TLS version range");
+}
+
+#[tokio::test]
+async fn proxy_protocol_header() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = std::thread::spawn(move || {
+        let (mut sock, _) = listener.accept().unwrap();
+        let mut buf = Vec::new();
+        let mut chunk = [0; 1024];
+        while !buf.ends_with(b"\r\n\r\n") {
+            let n = sock.read(&mut chunk).unwrap();
+            assert_ne!(n, 0, "unexpected eof");
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        sock.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    });
+
+    let source = "203.0.113.7:51234".parse().unwrap();
+    let res = reqwest::Client::builder()
+        .proxy_protocol(reqwest::ProxyProtocol::v1(source))
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(&format!("http://{}/", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+    let received = server.join().unwrap();
+    let header = format!("PROXY TCP4 203.0.113.7 127.0.0.1 51234 {}\r\n", addr.port());
+    assert!(received.starts_with(&header), "{:?}", received);
+    assert!(received[header.len()..].starts_with("GET / HTTP/1.1\r\n"));
+}
+
+#[tokio::test]
+async fn response_timings() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let client = Client::new();
+    let url = format!("http://{}/timings", server.addr());
+
+    let mut res = client.get(&url).send().await.unwrap();
+    let timings = res.timings().expect("timings");
+    assert!(!timings.is_reused());
+    // no lookup for an IP address
+    assert_eq!(timings.dns(), None);
+    assert!(timings.connect().is_some());
+    assert_eq!(timings.tls(), None);
+    assert!(timings.request_sent().is_some());
+    assert!(timings.first_byte().is_some());
+    assert_eq!(timings.body_complete(), None);
+
+    while let Some(_chunk) = res.chunk().await.unwrap() {}
+    assert!(res.timings().unwrap().body_complete().is_some());
+
+    let res = client.get(&url).send().await.unwrap();
+    let timings = res.timings().expect("timings");
+    assert!(timings.is_reused());
+    assert_eq!(timings.connect(), None);
+    assert!(timings.first_byte().is_some());
+}
+
+#[tokio::test]
+async fn pool_stats_and_close_idle_connections() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let client = Client::new();
+    let host = format!("http://{}", server.addr());
+    let url = format!("{}/pool", host);
+
+    let res = client.get(&url).send().await.unwrap();
+    let stats = client.pool_stats().host(&host).expect("host stats");
+    assert_eq!((stats.idle(), stats.active()), (0, 1));
+
+    res.text().await.unwrap();
+    let stats = client.pool_stats().host(&host).expect("host stats");
+    assert_eq!((stats.idle(), stats.active()), (1, 0));
+
+    client.close_idle_connections();
+    // the connection task notices the pool is gone on its next poll
+    for _ in 0..100 {
+        if client.pool_stats().idle() == 0 {
+            break;
+        }
+        tokio::time::delay_for(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(client.pool_stats().hosts().count(), 0);
+}
+
+#[tokio::test]
+async fn shutdown_waits_for_in_flight_requests() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let client = Client::new();
+    let url = format!("http://{}/shutdown", server.addr());
+
+    let res = client.get(&url).send().await.unwrap();
+
+    let mut shutdown = Box::pin(client.shutdown());
+    let waited = tokio::time::timeout(std::time::Duration::from_millis(50), &mut shutdown).await;
+    assert!(waited.is_err(), "shutdown waits for the response body");
+
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_request());
+
+    res.text().await.unwrap();
+    shutdown.await;
+}
+
+#[tokio::test]
+async fn shutdown_timeout_with_unread_body() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let client = Client::new();
+    let url = format!("http://{}/shutdown", server.addr());
+
+    // the body is never read
+    let _res = client.get(&url).send().await.unwrap();
+
+    let done = client
+        .shutdown_timeout(std::time::Duration::from_millis(50))
+        .await;
+    assert!(!done);
+
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_request());
+}
+
+#[tokio::test]
+async fn pool_max_connections_per_host_queues_requests() {
+    let server = server::http(move |_req| async {
+        tokio::time::delay_for(std::time::Duration::from_millis(50)).await;
+        http::Response::new("Hello".into())
+    });
+
+    let client = Client::builder()
+        .pool_max_connections_per_host(1)
+        .build()
+        .unwrap();
+    let url = format!("http://{}/queue", server.addr());
+
+    let requests = (0..3).map(|_| async {
+        client.get(&url).send().await.unwrap().text().await.unwrap()
+    });
+    for body in futures_util::future::join_all(requests).await {
+        assert_eq!(body, "Hello");
+    }
+
+    let stats = client.pool_stats();
+    assert_eq!((stats.idle(), stats.active()), (1, 0));
+}
+
+#[tokio::test]
+async fn pool_max_connections_per_host_serves_in_order() {
+    use std::sync::{Arc, Mutex};
+
+    let paths = Arc::new(Mutex::new(Vec::new()));
+    let seen = paths.clone();
+    let server = server::http(move |req| {
+        seen.lock().unwrap().push(req.uri().path().to_owned());
+        async {
+            tokio::time::delay_for(std::time::Duration::from_millis(20)).await;
+            http::Response::new("Hello".into())
+        }
+    });
+
+    let client = Client::builder()
+        .pool_max_connections_per_host(1)
+        .build()
+        .unwrap();
+
+    let requests = (0..4).map(|n| {
+        let url = format!("http://{}/{}", server.addr(), n);
+        let client = client.clone();
+        async move { client.get(&url).send().await.unwrap().text().await.unwrap() }
+    });
+    futures_util::future::join_all(requests).await;
+
+    assert_eq!(*paths.lock().unwrap(), ["/0", "/1", "/2", "/3"]);
+    let stats = client.pool_stats();
+    assert_eq!((stats.idle(), stats.active()), (1, 0));
+}
+
+#[tokio::test]
+async fn pool_max_connections_closes_idle_connections_to_other_hosts() {
+    use std::time::Duration;
+
+    let a = server::http(move |_req| async {
+        tokio::time::delay_for(Duration::from_millis(20)).await;
+        http::Response::new("A".into())
+    });
+    let b = server::http(move |_req| async { http::Response::new("B".into()) });
+
+    let client = Client::builder().pool_max_connections(2).build().unwrap();
+
+    let url = format!("http://{}/", a.addr());
+    let requests = (0..2).map(|_| async {
+        client.get(&url).send().await.unwrap().text().await.unwrap()
+    });
+    futures_util::future::join_all(requests).await;
+    assert_eq!(client.pool_stats().idle(), 2);
+
+    // the idle connections to `a` take all the room
+    let url = format!("http://{}/", b.addr());
+    let res = tokio::time::timeout(Duration::from_secs(5), client.get(&url).send())
+        .await
+        .expect("request to b waited for the idle connections to a")
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "B");
+
+    let stats = client.pool_stats();
+    let a_stats = stats.host(&format!("http://{}", a.addr())).unwrap();
+    assert_eq!((a_stats.idle(), a_stats.active()), (1, 0));
+}
+
+#[tokio::test]
+async fn preconnect_opens_idle_connections() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let server = server::http(move |_req| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        async { http::Response::new("Hello".into()) }
+    });
+
+    let client = Client::new();
+    let host = format!("http://{}", server.addr());
+
+    client.preconnect_n(&host, 2).await.unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 0);
+    let stats = client.pool_stats().host(&host).expect("host stats");
+    assert_eq!((stats.idle(), stats.active()), (2, 0));
+
+    let res = client.get(&host).send().await.unwrap();
+    assert!(res.timings().unwrap().is_reused());
+    res.text().await.unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    // the request used one of the preconnected connections
+    let stats = client.pool_stats().host(&host).expect("host stats");
+    assert_eq!((stats.idle(), stats.active()), (2, 0));
+}
+
+#[tokio::test]
+async fn preconnect_error() {
+    // grab a local port that nothing listens on
+    let down = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let url = format!("http://{}/", down);
+
+    let client = Client::new();
+    let err = client.preconnect(&url).await.unwrap_err();
+    assert!(err.is_request());
+    assert_eq!(err.url().map(|u| u.as_str()), Some(url.as_str()));
+    assert_eq!(client.pool_stats().hosts().count(), 0);
+}
+
+#[tokio::test]
+async fn preconnect_closed_by_server() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut listener = tokio::net::TcpListener::bind(std::net::SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let host = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        // close the preconnected connection right away
+        let (sock, _) = listener.accept().await.unwrap();
+        drop(sock);
+
+        let (mut sock, _) = listener.accept().await.unwrap();
+        let mut buf = [0; 4096];
+        let _ = sock.read(&mut buf).await.unwrap();
+        sock.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nHello")
+            .await
+            .unwrap();
+    });
+
+    let client = Client::new();
+    client.preconnect(&host).await.unwrap();
+
+    let idle = |client: &Client| client.pool_stats().host(&host).map_or(0, |s| s.idle());
+    for _ in 0..50 {
+        if idle(&client) == 0 {
+            break;
+        }
+        tokio::time::delay_for(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(idle(&client), 0);
+
+    let res = client.get(&host).send().await.unwrap();
+    assert!(!res.timings().unwrap().is_reused());
+    assert_eq!(res.text().await.unwrap(), "Hello");
+}
+
+#[tokio::test]
+async fn preconnect_idle_timeout() {
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let client = Client::builder()
+        .pool_idle_timeout(std::time::Duration::from_millis(50))
+        .build()
+        .unwrap();
+    let host = format!("http://{}", server.addr());
+
+    client.preconnect(&host).await.unwrap();
+    assert_eq!(client.pool_stats().host(&host).expect("host stats").idle(), 1);
+
+    tokio::time::delay_for(std::time::Duration::from_millis(300)).await;
+    assert_eq!(client.pool_stats().host(&host).map_or(0, |s| s.idle()), 0);
+}
+
+#[tokio::test]
+async fn preconnect_pool_max_connections_per_host() {
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let client = Client::builder()
+        .pool_max_connections_per_host(1)
+        .build()
+        .unwrap();
+    let host = format!("http://{}", server.addr());
+
+    client.preconnect(&host).await.unwrap();
+    // the second connection waits for room
+    let second = tokio::time::timeout(
+        std::time::Duration::from_millis(100),
+        client.preconnect(&host),
+    );
+    assert!(second.await.is_err());
+    assert_eq!(client.pool_stats().host(&host).expect("host stats").idle(), 1);
+}
+
+#[tokio::test]
+async fn ip_family() {
+    let server = server::http(move |_req| async { http::Response::default() });
+    let url = format!("http://localhost:{}/", server.addr().port());
+
+    let client = Client::builder()
+        .ip_family(reqwest::IpFamily::V4Only)
+        .build()
+        .unwrap();
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.remote_addr(), Some(server.addr()));
+
+    // the server only listens on IPv4
+    let client = Client::builder()
+        .ip_family(reqwest::IpFamily::V6Only)
+        .build()
+        .unwrap();
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_request());
+}
+
+#[tokio::test]
+async fn socket_config() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let server = server::http(move |_req| async { http::Response::default() });
+    let url = format!("http://{}/", server.addr());
+
+    let configured = Arc::new(AtomicUsize::new(0));
+    let counter = configured.clone();
+    let client = Client::builder()
+        .tcp_keepalive(Duration::from_secs(60))
+        .socket_config(move |socket| {
+            // the options of reqwest are set first
+            assert_eq!(socket.keepalive()?, Some(Duration::from_secs(60)));
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .build()
+        .unwrap();
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.remote_addr(), Some(server.addr()));
+    assert_eq!(configured.load(Ordering::SeqCst), 1);
+
+    // resolved by reqwest, since hyper doesn't connect the sockets
+    let url = format!("http://localhost:{}/", server.addr().port());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.remote_addr(), Some(server.addr()));
+
+    let client = Client::builder()
+        .socket_config(|_| Err(std::io::ErrorKind::PermissionDenied.into()))
+        .build()
+        .unwrap();
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_request());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn interface() {
+    let server = server::http(move |_req| async { http::Response::default() });
+    let url = format!("http://{}/", server.addr());
+
+    let client = Client::builder().interface("lo").build().unwrap();
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+    let client = Client::builder()
+        .interface("reqwest-none0")
+        .build()
+        .unwrap();
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_request());
+}
+
+#[tokio::test]
+async fn connection_info() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+    let url = format!("http://{}/", server.addr());
+
+    let client = Client::new();
+    let res = client.get(&url).send().await.unwrap();
+    let info = res.connection_info().expect("connection info");
+    res.text().await.unwrap();
+
+    let local_addr = info.local_addr().expect("local addr");
+    assert_eq!(local_addr.ip(), server.addr().ip());
+    assert_ne!(local_addr.port(), 0);
+    assert_eq!(info.remote_addr(), Some(server.addr()));
+    assert!(!info.is_reused());
+    assert!(!info.is_proxied());
+    assert_eq!(info.alpn_protocol(), None);
+
+    let res = client.get(&url).send().await.unwrap();
+    let info = res.connection_info().expect("connection info");
+    assert!(info.is_reused());
+    assert_eq!(info.local_addr(), Some(local_addr));
+}
@@ -27,6 +27,10 @@ async fn http_proxy() {
 
     assert_eq!(res.url().as_str(), url);
     assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+    let info = res.connection_info().expect("connection info");
+    assert!(info.is_proxied());
+    assert_eq!(info.remote_addr(), Some(server.addr()));
 }
 
 #[tokio::test]
@@ -178,3 +182,467 @@ async fn http_over_http() {
     assert_eq!(res.url().as_str(), url);
     assert_eq!(res.status(), reqwest::StatusCode::OK);
 }
+
+#[tokio::test]
+async fn http_proxy_per_request() {
+    let url = "http://hyper.rs/prox";
+    let server = server::http(move |req| {
+        assert_eq!(req.method(), "GET");
+        assert_eq!(req.uri(), url);
+        assert_eq!(req.headers()["host"], "hyper.rs");
+        assert_eq!(
+            req.headers()["proxy-authorization"],
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+
+        async { http::Response::default() }
+    });
+
+    let proxy = format!("http://Aladdin:open sesame@{}", server.addr());
+
+    let res = reqwest::Client::builder()
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(url)
+        .proxy(reqwest::Proxy::http(&proxy).unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.url().as_str(), url);
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn http_proxy_failover_per_request() {
+    let url = "http://hyper.rs/prox";
+    let server = server::http(move |req| {
+        assert_eq!(req.uri(), url);
+
+        async { http::Response::default() }
+    });
+
+    let down = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let secondary = format!("http://{}", server.addr());
+
+    let res = reqwest::Client::builder()
+        .no_proxy()
+        .proxy_failover(std::time::Duration::from_secs(60))
+        .build()
+        .unwrap()
+        .get(url)
+        .proxy(reqwest::Proxy::http(&format!("http://{}", down)).unwrap())
+        .proxy(reqwest::Proxy::http(&secondary).unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        res.proxy_url().map(|u| u.as_str()),
+        Some(format!("{}/", secondary).as_str())
+    );
+}
+
+#[tokio::test]
+async fn no_proxy_per_request() {
+    let server = server::http(move |req| {
+        assert_eq!(req.method(), "GET");
+        assert_eq!(req.uri(), "/4");
+
+        async { http::Response::default() }
+    });
+    let proxy = format!("http://{}", server.addr());
+    let url = format!("http://{}/4", server.addr());
+
+    let client = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::http(&proxy).unwrap())
+        .build()
+        .unwrap();
+
+    // the client's proxy would send the absolute-form uri
+    let res = client.get(&url).no_proxy().send().await.unwrap();
+
+    assert_eq!(res.url().as_str(), &url);
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn http_proxy_failover() {
+    let url = "http://hyper.rs/prox";
+    let server = server::http(move |req| {
+        assert_eq!(req.method(), "GET");
+        assert_eq!(req.uri(), url);
+        assert_eq!(req.headers()["host"], "hyper.rs");
+
+        async { http::Response::default() }
+    });
+
+    // grab a local port that nothing listens on
+    let down = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let primary = format!("http://{}", down);
+    let secondary = format!("http://{}", server.addr());
+
+    let client = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::http(&primary).unwrap())
+        .proxy(reqwest::Proxy::http(&secondary).unwrap())
+        .proxy_failover(std::time::Duration::from_secs(60))
+        .pool_max_idle_per_host(0)
+        .build()
+        .unwrap();
+
+    for _ in 0..2 {
+        let res = client.get(url).send().await.unwrap();
+
+        assert_eq!(res.url().as_str(), url);
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            res.proxy_url().map(|u| u.as_str()),
+            Some(format!("{}/", secondary).as_str())
+        );
+    }
+}
+
+#[tokio::test]
+async fn http_proxy_failover_basic_auth() {
+    let url = "http://hyper.rs/prox";
+    let server = server::http(move |req| {
+        assert_eq!(req.uri(), url);
+        assert_eq!(
+            req.headers()["proxy-authorization"],
+            "Basic c2hhcmVkOnBhc3M="
+        );
+
+        async { http::Response::default() }
+    });
+    let other = server::http(move |_req| async {
+        panic!("proxy with other credentials should not be used");
+    });
+
+    let down = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let primary = format!("http://{}", down);
+    let secondary = format!("http://{}", server.addr());
+
+    let res = reqwest::Client::builder()
+        .proxy(
+            reqwest::Proxy::http(&primary)
+                .unwrap()
+                .basic_auth("shared", "pass"),
+        )
+        .proxy(
+            reqwest::Proxy::http(&secondary)
+                .unwrap()
+                .basic_auth("shared", "pass"),
+        )
+        .proxy_failover(std::time::Duration::from_secs(60))
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+    // the header already carries the primary's credentials, so a secondary
+    // with other credentials isn't failed over to
+    let secondary = format!("http://{}", other.addr());
+
+    let err = reqwest::Client::builder()
+        .proxy(
+            reqwest::Proxy::http(&primary)
+                .unwrap()
+                .basic_auth("primary", "pass"),
+        )
+        .proxy(
+            reqwest::Proxy::http(&secondary)
+                .unwrap()
+                .basic_auth("secondary", "pass"),
+        )
+        .proxy_failover(std::time::Duration::from_secs(60))
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_request());
+}
+
+#[tokio::test]
+async fn http_proxy_no_failover() {
+    let url = "http://hyper.rs/prox";
+    let primary = server::http(move |req| {
+        assert_eq!(req.uri(), url);
+        assert_eq!(
+            req.headers()["proxy-authorization"],
+            "Basic cHJpbWFyeTpwYXNz"
+        );
+
+        async { http::Response::default() }
+    });
+    let secondary = server::http(move |_req| async {
+        panic!("secondary proxy should not be used");
+    });
+
+    let down = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let client = reqwest::Client::builder()
+        .proxy(
+            reqwest::Proxy::http(&format!("http://{}", primary.addr()))
+                .unwrap()
+                .basic_auth("primary", "pass"),
+        )
+        .proxy(
+            reqwest::Proxy::http(&format!("http://{}", secondary.addr()))
+                .unwrap()
+                .basic_auth("secondary", "pass"),
+        )
+        .build()
+        .unwrap();
+
+    let res = client.get(url).send().await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+    // without failover, a down primary is an error
+    let err = reqwest::Client::builder()
+        .proxy(
+            reqwest::Proxy::http(&format!("http://{}", down))
+                .unwrap()
+                .basic_auth("primary", "pass"),
+        )
+        .proxy(
+            reqwest::Proxy::http(&format!("http://{}", secondary.addr()))
+                .unwrap()
+                .basic_auth("secondary", "pass"),
+        )
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_request());
+}
+
+#[cfg(feature = "pac")]
+#[tokio::test]
+async fn http_proxy_pac() {
+    let url = "http://hyper.rs/prox";
+    let server = server::http(move |req| {
+        assert_eq!(req.uri(), url);
+        assert_eq!(req.headers()["host"], "hyper.rs");
+
+        async { http::Response::default() }
+    });
+
+    let down = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let script = format!(
+        r#"
+        function FindProxyForURL(url, host) {{
+            if (dnsDomainIs(host, "hyper.rs")) {{
+                return "PROXY {}; PROXY {}";
+            }}
+            return "DIRECT";
+        }}
+        "#,
+        down,
+        server.addr()
+    );
+
+    let res = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::pac(script).unwrap())
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        res.proxy_url().map(|u| u.as_str()),
+        Some(format!("http://{}/", server.addr()).as_str())
+    );
+}
+
+#[cfg(feature = "pac")]
+#[tokio::test]
+async fn http_proxy_pac_by_path() {
+    let api = server::http(move |req| {
+        assert_eq!(req.uri(), "http://hyper.rs/api/prox");
+
+        async { http::Response::default() }
+    });
+    let www = server::http(move |req| {
+        assert_eq!(req.uri(), "http://hyper.rs/www/prox");
+
+        async {
+            http::Response::builder()
+                .status(302)
+                .header("location", "/api/prox")
+                .body(Default::default())
+                .unwrap()
+        }
+    });
+
+    let script = format!(
+        r#"
+        function FindProxyForURL(url, host) {{
+            if (shExpMatch(url, "*/api/*")) {{
+                return "PROXY {}";
+            }}
+            return "PROXY {}";
+        }}
+        "#,
+        api.addr(),
+        www.addr()
+    );
+
+    // the redirect goes through the proxy for its own path
+    let res = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::pac(script).unwrap())
+        .build()
+        .unwrap()
+        .get("http://hyper.rs/www/prox")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        res.proxy_url().map(|u| u.as_str()),
+        Some(format!("http://{}/", api.addr()).as_str())
+    );
+}
+
+#[cfg(feature = "pac")]
+#[test]
+fn http_proxy_pac_send_outside_runtime() {
+    let script = r#"function FindProxyForURL(url, host) { return "DIRECT"; }"#;
+    let client = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::pac(script).unwrap())
+        .build()
+        .unwrap();
+
+    // the script only runs once the future is polled
+    drop(client.get("http://hyper.rs/prox").send());
+}
+
+#[cfg(feature = "pac")]
+#[tokio::test]
+async fn http_proxy_pac_timeout() {
+    let script = r#"
+        function FindProxyForURL(url, host) {
+            var end = Date.now() + 2000;
+            while (Date.now() < end) {}
+            return "DIRECT";
+        }
+    "#;
+
+    let start = std::time::Instant::now();
+    let err = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::pac(script).unwrap())
+        .timeout(std::time::Duration::from_millis(200))
+        .build()
+        .unwrap()
+        .get("http://hyper.rs/prox")
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_timeout(), "{:?}", err);
+    assert!(start.elapsed() < std::time::Duration::from_millis(1500));
+}
+
+#[cfg(feature = "pac")]
+#[tokio::test]
+async fn http_proxy_pac_basic_auth() {
+    let url = "http://hyper.rs/prox";
+    let server = server::http(move |req| {
+        assert_eq!(req.uri(), url);
+        assert_eq!(
+            req.headers()["proxy-authorization"],
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+
+        async { http::Response::default() }
+    });
+
+    let down = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let script = format!(
+        r#"
+        function FindProxyForURL(url, host) {{
+            return "PROXY {}; PROXY {}";
+        }}
+        "#,
+        down,
+        server.addr()
+    );
+
+    let res = reqwest::Client::builder()
+        .proxy(
+            reqwest::Proxy::pac(script)
+                .unwrap()
+                .basic_auth("Aladdin", "open sesame"),
+        )
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn http_proxy_preconnect() {
+    let url = "http://hyper.rs/prox";
+    let server = server::http(move |req| {
+        assert_eq!(req.uri(), url);
+
+        async { http::Response::default() }
+    });
+
+    let proxy = format!("http://{}", server.addr());
+
+    let client = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::http(&proxy).unwrap())
+        .build()
+        .unwrap();
+
+    client.preconnect(url).await.unwrap();
+    let stats = client.pool_stats().host("http://hyper.rs").expect("host stats");
+    assert_eq!(stats.idle(), 1);
+
+    let res = client.get(url).send().await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert!(res.timings().unwrap().is_reused());
+}
@@ -314,3 +314,79 @@ async fn test_redirect_302_with_set_cookies() {
     assert_eq!(res.url().as_str(), dst);
     assert_eq!(res.status(), reqwest::StatusCode::OK);
 }
+
+#[tokio::test]
+async fn test_redirect_propagates_trace_context() {
+    use reqwest::propagation::{Propagation, SpanContext, TraceContext};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    let server = server::http(move |req| async move {
+        let traceparent = req.headers()["traceparent"].to_str().unwrap().to_owned();
+        if req.uri() == "/start" {
+            assert_eq!(
+                traceparent,
+                "00-0000000000000000000000000000002a-0000000000000001-01"
+            );
+            http::Response::builder()
+                .status(302)
+                .header("location", "/dst")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            assert_eq!(
+                traceparent,
+                "00-0000000000000000000000000000002a-0000000000000002-01"
+            );
+            http::Response::default()
+        }
+    });
+
+    let span_ids = AtomicU64::new(0);
+    let client = reqwest::Client::builder()
+        .propagation(Propagation::new(TraceContext, move || {
+            Some(SpanContext::new(42, span_ids.fetch_add(1, Ordering::SeqCst) + 1))
+        }))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/start", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_redirect_keeps_caller_trace_context() {
+    use reqwest::propagation::{Propagation, SpanContext, TraceContext};
+
+    let traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["traceparent"], traceparent);
+        assert_eq!(req.headers()["tracestate"], "rojo=00f067aa0ba902b7");
+        if req.uri() == "/start" {
+            http::Response::builder()
+                .status(302)
+                .header("location", "/dst")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            http::Response::default()
+        }
+    });
+
+    let client = reqwest::Client::builder()
+        .propagation(Propagation::new(TraceContext, || {
+            Some(SpanContext::new(42, 1))
+        }))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/start", server.addr());
+    let res = client
+        .get(&url)
+        .header("traceparent", traceparent)
+        .header("tracestate", "rojo=00f067aa0ba902b7")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
@@ -1,4 +1,8 @@
 pub mod server;
+// Only some tests need a TLS server.
+#[cfg(feature = "__tls")]
+#[allow(unused)]
+pub mod tls;
 
 // TODO: remove once done converting to new support server?
 #[allow(unused)]
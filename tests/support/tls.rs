@@ -0,0 +1,335 @@
+//! HTTPS servers with a self-signed certificate for `localhost`, on rustls
+//! or native-tls.
+
+#[cfg(feature = "rustls-tls")]
+use std::convert::Infallible;
+use std::io::{self, Read, Write};
+use std::net::{self, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[cfg(feature = "rustls-tls")]
+use rustls::internal::pemfile;
+
+/// The certificate of the server, in PEM.
+pub static CERT_PEM: &[u8] = include_bytes!("localhost-cert.pem");
+
+/// The certificate of the server, in DER.
+pub fn cert_der() -> Vec<u8> {
+    let pem = std::str::from_utf8(CERT_PEM).expect("cert");
+    let base64: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+    base64::decode(&base64).expect("cert")
+}
+
+/// The base64 SHA-256 of the public key of `CERT_PEM`.
+pub static CERT_PIN: &str = "sha256/4f3HKGirRuXXklx2asUYB0OFiAh0gg9Stp4pWIcUsKI=";
+
+pub struct Server {
+    addr: net::SocketAddr,
+    conns: Arc<Mutex<Vec<Accepted>>>,
+}
+
+/// What the server saw of the handshake of a connection.
+struct Accepted {
+    sni: Option<String>,
+    resumed: bool,
+}
+
+impl Server {
+    pub fn addr(&self) -> net::SocketAddr {
+        self.addr
+    }
+
+    /// The SNI names that clients sent, by connection.
+    pub fn sni_names(&self) -> Vec<Option<String>> {
+        self.conns.lock().unwrap().iter().map(|conn| conn.sni.clone()).collect()
+    }
+
+    /// Whether the clients resumed a TLS session, by connection.
+    pub fn resumed(&self) -> Vec<bool> {
+        self.conns.lock().unwrap().iter().map(|conn| conn.resumed).collect()
+    }
+
+    /// The URL of `path` on this server, by the name its certificate is for.
+    pub fn url(&self, path: &str) -> String {
+        format!("https://localhost:{}{}", self.addr.port(), path)
+    }
+}
+
+/// The private key of `CERT_PEM`, in PKCS #8 PEM.
+pub static KEY_PEM: &[u8] = include_bytes!("localhost-key.pem");
+
+#[cfg(feature = "rustls-tls")]
+/// A server config with the `localhost` certificate.
+pub fn config() -> rustls::ServerConfig {
+    with_cert(rustls::ServerConfig::new(rustls::NoClientAuth::new()))
+}
+
+#[cfg(feature = "rustls-tls")]
+/// A server config with the `localhost` certificate, that requires clients
+/// to present the `localhost` certificate too.
+pub fn config_with_client_auth() -> rustls::ServerConfig {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_pem_file(&mut &CERT_PEM[..]).expect("client root");
+    with_cert(rustls::ServerConfig::new(
+        rustls::AllowAnyAuthenticatedClient::new(roots),
+    ))
+}
+
+#[cfg(feature = "rustls-tls")]
+/// A server config with the `localhost` certificate, that prefers h2 over
+/// HTTP/1.1 with ALPN.
+pub fn config_with_h2() -> rustls::ServerConfig {
+    let mut config = config();
+    config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+    config
+}
+
+#[cfg(feature = "rustls-tls")]
+fn with_cert(mut config: rustls::ServerConfig) -> rustls::ServerConfig {
+    let certs = pemfile::certs(&mut &CERT_PEM[..]).expect("cert");
+    let key = pemfile::pkcs8_private_keys(&mut &KEY_PEM[..])
+        .expect("key")
+        .remove(0);
+    config.set_single_cert(certs, key).expect("server cert");
+    config
+}
+
+#[cfg(feature = "rustls-tls")]
+/// Starts a server answering every request with `Hello`.
+pub fn https(config: rustls::ServerConfig) -> Server {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    let config = Arc::new(config);
+    let conns = Arc::new(Mutex::new(Vec::new()));
+
+    let server_conns = conns.clone();
+    thread::spawn(move || {
+        for tcp in listener.incoming() {
+            let tcp = match tcp {
+                Ok(tcp) => tcp,
+                Err(_) => return,
+            };
+            let config = config.clone();
+            let conns = server_conns.clone();
+            thread::spawn(move || serve(&config, &conns, tcp));
+        }
+    });
+
+    Server { addr, conns }
+}
+
+#[cfg(feature = "rustls-tls")]
+fn serve(
+    config: &Arc<rustls::ServerConfig>,
+    conns: &Mutex<Vec<Accepted>>,
+    tcp: TcpStream,
+) {
+    let session = rustls::ServerSession::new(config);
+    let tls = rustls::StreamOwned::new(session, tcp);
+    respond(tls, conns, |tls| Accepted {
+        sni: tls.sess.get_sni_hostname().map(str::to_owned),
+        resumed: tls.sess.received_resumption_data().is_some(),
+    });
+}
+
+/// Starts a native-tls server answering every request with `Hello`.
+///
+/// It never resumes sessions.
+#[cfg(feature = "default-tls")]
+pub fn native_https() -> Server {
+    let identity = native_tls_crate::Identity::from_pkcs8(CERT_PEM, KEY_PEM).expect("identity");
+    let acceptor = Arc::new(native_tls_crate::TlsAcceptor::new(identity).expect("acceptor"));
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    let conns = Arc::new(Mutex::new(Vec::new()));
+
+    let server_conns = conns.clone();
+    thread::spawn(move || {
+        for tcp in listener.incoming() {
+            let tcp = match tcp {
+                Ok(tcp) => tcp,
+                Err(_) => return,
+            };
+            let acceptor = acceptor.clone();
+            let conns = server_conns.clone();
+            thread::spawn(move || {
+                // native-tls doesn't tell the SNI name, so it's read from
+                // the ClientHello before the handshake.
+                let sni = peek_sni(&tcp);
+                if let Ok(tls) = acceptor.accept(tcp) {
+                    respond(tls, &conns, |_| Accepted { sni, resumed: false });
+                }
+            });
+        }
+    });
+
+    Server { addr, conns }
+}
+
+/// The SNI name of the ClientHello that `tcp` starts with, left to read.
+#[cfg(feature = "default-tls")]
+fn peek_sni(tcp: &TcpStream) -> Option<String> {
+    let mut buf = vec![0; 5 + 0x4000];
+    let hello = loop {
+        let n = tcp.peek(&mut buf).ok()?;
+        if n == 0 {
+            return None;
+        }
+        // The first record, after its type, version and length.
+        if let Some((hello, _)) = buf[..n].get(3..).and_then(|record| field(record, 2)) {
+            break hello;
+        }
+        thread::sleep(std::time::Duration::from_millis(1));
+    };
+
+    // Skip the handshake type and length, the version and the random.
+    let rest = hello.get(4 + 2 + 32..)?;
+    let (_session_id, rest) = field(rest, 1)?;
+    let (_cipher_suites, rest) = field(rest, 2)?;
+    let (_compression_methods, rest) = field(rest, 1)?;
+    let (mut extensions, _) = field(rest, 2)?;
+    while extensions.len() >= 2 {
+        let (data, rest) = field(&extensions[2..], 2)?;
+        if extensions[..2] == [0, 0] {
+            // A list with a single `host_name`.
+            let (list, _) = field(data, 2)?;
+            let (name, _) = field(list.get(1..)?, 2)?;
+            return String::from_utf8(name.to_vec()).ok();
+        }
+        extensions = rest;
+    }
+    None
+}
+
+/// Splits the field `buf` starts with, after its length in `size` bytes.
+#[cfg(feature = "default-tls")]
+fn field(buf: &[u8], size: usize) -> Option<(&[u8], &[u8])> {
+    let len = buf
+        .get(..size)?
+        .iter()
+        .fold(0, |len, &byte| len << 8 | usize::from(byte));
+    let rest = &buf[size..];
+    if rest.len() < len {
+        return None;
+    }
+    Some(rest.split_at(len))
+}
+
+/// Answers the requests on `tls` with `Hello`, recording the connection
+/// with `accepted` once its handshake is done.
+fn respond<S, F>(mut tls: S, conns: &Mutex<Vec<Accepted>>, accepted: F)
+where
+    S: Read + Write,
+    F: FnOnce(&S) -> Accepted,
+{
+    let mut accepted = Some(accepted);
+    let mut buf = Vec::new();
+    let mut chunk = [0; 1024];
+
+    loop {
+        let end = loop {
+            if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+            match tls.read(&mut chunk) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        };
+        buf.drain(..end);
+
+        if let Some(accepted) = accepted.take() {
+            conns.lock().unwrap().push(accepted(&tls));
+        }
+
+        let res = b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nHello";
+        if tls.write_all(res).and_then(|()| tls.flush()).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(feature = "rustls-tls")]
+/// Starts a hyper server, speaking HTTP/1 or h2, answering every request with
+/// its HTTP version, such as `HTTP/2.0`.
+pub fn hyper_https(config: rustls::ServerConfig) -> Server {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+    thread::spawn(move || {
+        let mut rt = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .expect("new rt");
+        rt.block_on(async move {
+            let mut listener = tokio::net::TcpListener::from_std(listener).expect("listener");
+            while let Ok((tcp, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let tls = match acceptor.accept(tcp).await {
+                        Ok(tls) => tls,
+                        Err(_) => return,
+                    };
+                    let service = hyper::service::service_fn(|req: hyper::Request<hyper::Body>| {
+                        let body = hyper::Body::from(format!("{:?}", req.version()));
+                        async move { Ok::<_, Infallible>(hyper::Response::new(body)) }
+                    });
+                    let _ = hyper::server::conn::Http::new()
+                        .serve_connection(tls, service)
+                        .await;
+                });
+            }
+        });
+    });
+
+    Server {
+        addr,
+        conns: Arc::default(),
+    }
+}
+
+/// Starts an HTTP proxy that only tunnels, with `CONNECT`.
+pub fn tunnel_proxy() -> net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+
+    thread::spawn(move || {
+        for tcp in listener.incoming() {
+            let tcp = match tcp {
+                Ok(tcp) => tcp,
+                Err(_) => return,
+            };
+            thread::spawn(move || {
+                let _ = tunnel(tcp);
+            });
+        }
+    });
+
+    addr
+}
+
+fn tunnel(mut client: TcpStream) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut byte = [0];
+    while !head.ends_with(b"\r\n\r\n") {
+        client.read_exact(&mut byte)?;
+        head.push(byte[0]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let target = head
+        .strip_prefix("CONNECT ")
+        .and_then(|rest| rest.split(' ').next())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not CONNECT"))?;
+    let mut server = TcpStream::connect(target)?;
+    client.write_all(b"HTTP/1.1 200 OK\r\n\r\n")?;
+
+    let mut client_read = client.try_clone()?;
+    let mut server_write = server.try_clone()?;
+    thread::spawn(move || io::copy(&mut client_read, &mut server_write));
+    io::copy(&mut server, &mut client)?;
+    Ok(())
+}
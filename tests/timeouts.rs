@@ -88,6 +88,193 @@ async fn response_timeout() {
     assert!(err.is_timeout());
 }
 
+#[tokio::test]
+async fn read_timeout() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |_req| {
+        async {
+            // delay returning the response
+            tokio::time::delay_for(Duration::from_secs(2)).await;
+            http::Response::default()
+        }
+    });
+
+    let client = reqwest::Client::builder()
+        .read_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/slow", server.addr());
+    let err = client.get(&url).send().await.unwrap_err();
+
+    assert!(err.is_timeout());
+    assert_eq!(err.url().map(|u| u.as_str()), Some(url.as_str()));
+}
+
+#[tokio::test]
+async fn read_timeout_resets_on_progress() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |_req| {
+        async {
+            // the whole body takes longer than the read timeout,
+            // but every chunk arrives in time
+            let chunks = futures_util::stream::unfold(0, |n| async move {
+                if n == 4 {
+                    return None;
+                }
+                tokio::time::delay_for(Duration::from_millis(200)).await;
+                Some((Ok::<_, std::convert::Infallible>("chunk"), n + 1))
+            });
+
+            http::Response::new(hyper::Body::wrap_stream(chunks))
+        }
+    });
+
+    let client = reqwest::Client::builder()
+        .read_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/chunks", server.addr());
+    let res = client.get(&url).send().await.expect("Failed to get");
+    let body = res.text().await.expect("Failed to read body");
+
+    assert_eq!(body, "chunkchunkchunkchunk");
+}
+
+#[tokio::test]
+async fn read_timeout_idle_pooled() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let client = reqwest::Client::builder()
+        .read_timeout(Duration::from_millis(300))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/idle", server.addr());
+    let res = client.get(&url).send().await.expect("first");
+    res.bytes().await.expect("first body");
+
+    // longer than the read timeout, with the connection idle in the pool
+    tokio::time::delay_for(Duration::from_millis(600)).await;
+
+    let res = client.get(&url).send().await.expect("second");
+    assert!(res.timings().expect("timings").is_reused());
+}
+
+#[tokio::test]
+async fn read_timeout_http2_per_request() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |req| async move {
+        if req.uri().path() == "/slow" {
+            tokio::time::delay_for(Duration::from_secs(2)).await;
+        }
+        http::Response::default()
+    });
+
+    let client = reqwest::Client::builder()
+        .http2_prior_knowledge()
+        .read_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+
+    // the fast response on the same connection doesn't stop the slow
+    // request from timing out
+    let slow = client.get(&format!("http://{}/slow", server.addr())).send();
+    let fast = async {
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+        client.get(&format!("http://{}/fast", server.addr())).send().await
+    };
+    let (slow, fast) = tokio::join!(slow, fast);
+
+    let fast = fast.expect("fast");
+    assert_eq!(fast.version(), http::Version::HTTP_2);
+    assert!(slow.unwrap_err().is_timeout());
+
+    // an idle connection isn't closed by the read timeout
+    tokio::time::delay_for(Duration::from_millis(700)).await;
+    let res = client
+        .get(&format!("http://{}/fast", server.addr()))
+        .send()
+        .await
+        .expect("idle");
+    assert!(res.timings().expect("timings").is_reused());
+
+    // close the connection, so the server can shut down gracefully
+    drop((fast, res));
+    drop(client);
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+}
+
+#[cfg(feature = "stream")]
+#[tokio::test]
+async fn read_timeout_slow_upload() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |req| async move {
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        assert_eq!(body, "chunkchunkchunkchunk");
+        http::Response::default()
+    });
+
+    let client = reqwest::Client::builder()
+        .read_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+
+    // the upload takes longer than the read timeout, with nothing to read
+    let chunks = futures_util::stream::unfold(0, |n| async move {
+        if n == 4 {
+            return None;
+        }
+        tokio::time::delay_for(Duration::from_millis(200)).await;
+        Some((Ok::<_, std::convert::Infallible>("chunk"), n + 1))
+    });
+
+    let url = format!("http://{}/upload", server.addr());
+    let res = client
+        .post(&url)
+        .body(reqwest::Body::wrap_stream(chunks))
+        .send()
+        .await
+        .expect("Failed to post");
+
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn read_timeout_body() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |_req| {
+        async {
+            // immediate response, but delayed body
+            let body = hyper::Body::wrap_stream(futures_util::stream::once(async {
+                tokio::time::delay_for(Duration::from_secs(2)).await;
+                Ok::<_, std::convert::Infallible>("Hello")
+            }));
+
+            http::Response::new(body)
+        }
+    });
+
+    let client = reqwest::Client::builder()
+        .read_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/slow", server.addr());
+    let res = client.get(&url).send().await.expect("Failed to get");
+    let err = res.text().await.unwrap_err();
+
+    assert!(err.is_timeout());
+}
+
 /// Tests that internal client future cancels when the oneshot channel
 /// is canceled.
 #[cfg(feature = "blocking")]
@@ -178,3 +365,67 @@ fn write_timeout_large_body() {
     assert!(err.is_timeout());
     assert_eq!(err.url().map(|u| u.as_str()), Some(url.as_str()));
 }
+
+#[tokio::test]
+async fn pool_timeout_while_queued() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |_req| async {
+        tokio::time::delay_for(Duration::from_millis(500)).await;
+        http::Response::default()
+    });
+
+    let client = reqwest::Client::builder()
+        .pool_max_connections_per_host(1)
+        .timeout(Duration::from_millis(300))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/slow", server.addr());
+
+    let first = client.get(&url).timeout(Duration::from_secs(5)).send();
+    let queued = async {
+        // let the first request take the connection
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+        client.get(&url).send().await
+    };
+    let (first, queued) = futures_util::future::join(first, queued).await;
+
+    assert_eq!(first.unwrap().status(), reqwest::StatusCode::OK);
+
+    let err = queued.unwrap_err();
+    assert!(err.is_timeout());
+    assert!(err.is_pool_timeout());
+}
+
+#[tokio::test]
+async fn timeout_while_another_is_queued() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |_req| async {
+        tokio::time::delay_for(Duration::from_millis(500)).await;
+        http::Response::default()
+    });
+
+    let client = reqwest::Client::builder()
+        .pool_max_connections_per_host(1)
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/slow", server.addr());
+
+    let first = client.get(&url).timeout(Duration::from_millis(300)).send();
+    let queued = async {
+        // let the first request take the connection
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+        client.get(&url).send().await
+    };
+    let (first, queued) = futures_util::future::join(first, queued).await;
+
+    // the first request had its connection, it's the other one that waits
+    let err = first.unwrap_err();
+    assert!(err.is_timeout());
+    assert!(!err.is_pool_timeout());
+
+    assert_eq!(queued.unwrap().status(), reqwest::StatusCode::OK);
+}
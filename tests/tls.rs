@@ -0,0 +1,644 @@
+#![cfg(feature = "__tls")]
+#[allow(unused)]
+mod support;
+use support::*;
+
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "rustls-tls")]
+use reqwest::{Certificate, Identity, TlsSessionCache};
+use reqwest::{Client, HostTlsConfig, IpFamily};
+
+#[cfg(feature = "rustls-tls")]
+fn rustls_client() -> reqwest::ClientBuilder {
+    Client::builder()
+        .use_rustls_tls()
+        .ip_family(IpFamily::V4Only)
+        .add_root_certificate(Certificate::from_pem(tls::CERT_PEM).unwrap())
+}
+
+// native-tls is the default backend without the `native-tls` feature.
+#[cfg(feature = "default-tls")]
+fn native_tls_client() -> reqwest::ClientBuilder {
+    let builder = Client::builder();
+    #[cfg(feature = "native-tls")]
+    let builder = builder.use_native_tls();
+    builder.ip_family(IpFamily::V4Only)
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_pin_certificate() {
+    let server = tls::https(tls::config());
+
+    let res = rustls_client()
+        .pin_certificate("localhost", tls::CERT_PIN)
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    let err = rustls_client()
+        .pin_certificate("*.localhost", &base64_zeros())
+        .pin_certificate("localhost", &base64_zeros())
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_certificate_pin(), "{:?}", err);
+    assert!(err.to_string().contains(tls::CERT_PIN), "{}", err);
+}
+
+#[cfg(feature = "default-tls")]
+#[tokio::test]
+async fn native_tls_pin_certificate() {
+    let server = tls::native_https();
+    let client = |pin: &str| {
+        native_tls_client()
+            .danger_accept_invalid_certs(true)
+            .pin_certificate("localhost", pin)
+            .build()
+            .unwrap()
+    };
+
+    let res = client(tls::CERT_PIN).get(&server.url("/")).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    let err = client(&base64_zeros())
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_certificate_pin(), "{:?}", err);
+    assert!(err.to_string().contains(tls::CERT_PIN), "{}", err);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn pin_certificate_through_proxy() {
+    let server = tls::https(tls::config());
+    let proxy = tls::tunnel_proxy();
+
+    let err = rustls_client()
+        .proxy(reqwest::Proxy::https(&format!("http://{}", proxy)).unwrap())
+        .pin_certificate("localhost", &base64_zeros())
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_certificate_pin(), "{:?}", err);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_verifier_accepts() {
+    let server = tls::https(tls::config());
+    let seen = Arc::new(Mutex::new(None));
+
+    let seen2 = seen.clone();
+    let client = Client::builder()
+        .use_rustls_tls()
+        .ip_family(IpFamily::V4Only)
+        .tls_verifier(move |info: &reqwest::ServerCertInfo| {
+            let host = info.host().to_owned();
+            *seen2.lock().unwrap() = Some((host, info.chain().len(), info.is_verified()));
+            if info.host() == "localhost" {
+                Ok(())
+            } else {
+                Err("untrusted host".into())
+            }
+        })
+        .build()
+        .unwrap();
+
+    let res = client.get(&server.url("/")).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    // The self-signed certificate isn't trusted, so only the callback
+    // accepted it.
+    let seen = seen.lock().unwrap().take();
+    assert_eq!(seen, Some(("localhost".to_owned(), 1, false)));
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_verifier_rejects() {
+    let server = tls::https(tls::config());
+
+    let err = rustls_client()
+        .tls_verifier(|info: &reqwest::ServerCertInfo| {
+            assert!(info.is_verified(), "{:?}", info.verify_error());
+            Err("missing the expected SAN".into())
+        })
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("missing the expected SAN"), "{:?}", err);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_verifier_with_invalid_certs() {
+    let server = tls::https(tls::config());
+
+    // Verification is skipped, even though the certificate is trusted, but
+    // the callback still decides.
+    let err = rustls_client()
+        .danger_accept_invalid_certs(true)
+        .tls_verifier(|info: &reqwest::ServerCertInfo| {
+            let err = info.verify_error().expect("not verified");
+            assert_eq!(err.to_string(), "certificate verification is disabled");
+            Err("rejected by callback".into())
+        })
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("rejected by callback"), "{:?}", err);
+}
+
+#[cfg(feature = "default-tls")]
+#[tokio::test]
+async fn native_tls_tls_verifier() {
+    let server = tls::native_https();
+    let client = |accept: bool| {
+        native_tls_client()
+            .danger_accept_invalid_certs(true)
+            .tls_verifier(move |info: &reqwest::ServerCertInfo| {
+                assert_eq!(info.host(), "localhost");
+                assert_eq!(info.chain().len(), 1);
+                assert!(!info.is_verified());
+                if accept {
+                    Ok(())
+                } else {
+                    Err("rejected by callback".into())
+                }
+            })
+            .build()
+            .unwrap()
+    };
+
+    let res = client(true).get(&server.url("/")).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    let err = client(false).get(&server.url("/")).send().await.unwrap_err();
+    assert!(err.to_string().contains("rejected by callback"), "{}", err);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_verifier_server_name() {
+    let server = tls::https(tls::config());
+    let hosts = Arc::new(Mutex::new(Vec::new()));
+
+    let seen = hosts.clone();
+    let res = rustls_client()
+        .tls_verifier(move |info: &reqwest::ServerCertInfo| {
+            seen.lock().unwrap().push(info.host().to_owned());
+            Ok(())
+        })
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .tls_server_name("example.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+    assert_eq!(*hosts.lock().unwrap(), ["example.com"]);
+}
+
+#[cfg(feature = "default-tls")]
+#[tokio::test]
+async fn native_tls_tls_verifier_server_name() {
+    let server = tls::native_https();
+    let hosts = Arc::new(Mutex::new(Vec::new()));
+
+    let seen = hosts.clone();
+    let res = native_tls_client()
+        .danger_accept_invalid_certs(true)
+        .tls_verifier(move |info: &reqwest::ServerCertInfo| {
+            seen.lock().unwrap().push(info.host().to_owned());
+            Ok(())
+        })
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .tls_server_name("example.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+    assert_eq!(*hosts.lock().unwrap(), ["example.com"]);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_info() {
+    let server = tls::https(tls::config());
+
+    let res = rustls_client()
+        .tls_info(true)
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+
+    let info = res.tls_info().expect("tls info");
+    assert_eq!(info.peer_certificates(), &[tls::cert_der()][..]);
+    assert_eq!(info.protocol_version(), Some(reqwest::TlsVersion::Tls1_3));
+    assert!(info.cipher_suite().unwrap().starts_with("TLS13_"), "{:?}", info);
+    assert_eq!(info.alpn_protocol(), None);
+    assert!(res.connection_info().is_some());
+
+    let res = rustls_client()
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.tls_info().is_none());
+}
+
+#[cfg(feature = "default-tls")]
+#[tokio::test]
+async fn native_tls_tls_info() {
+    let server = tls::native_https();
+
+    let res = native_tls_client()
+        .danger_accept_invalid_certs(true)
+        .tls_info(true)
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+
+    let info = res.tls_info().expect("tls info");
+    assert_eq!(info.peer_certificates(), &[tls::cert_der()][..]);
+    assert_eq!(info.protocol_version(), None);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_host_tls_config() {
+    let server = tls::https(tls::config_with_client_auth());
+    let partner = || {
+        HostTlsConfig::new()
+            .add_root_certificate(Certificate::from_pem(tls::CERT_PEM).unwrap())
+            .identity(Identity::from_pkcs8_pem(tls::CERT_PEM, tls::KEY_PEM).unwrap())
+    };
+    let client = |pattern: &str| {
+        Client::builder()
+            .use_rustls_tls()
+            .ip_family(IpFamily::V4Only)
+            .host_tls_config("other.example", HostTlsConfig::new())
+            .host_tls_config(pattern, partner())
+            .build()
+            .unwrap()
+    };
+
+    let res = client("localhost")
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    let err = client("*.localhost")
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_request(), "{:?}", err);
+
+    // The root certificate alone isn't enough, the server wants the identity.
+    let err = Client::builder()
+        .use_rustls_tls()
+        .ip_family(IpFamily::V4Only)
+        .host_tls_config(
+            "localhost",
+            HostTlsConfig::new()
+                .add_root_certificate(Certificate::from_pem(tls::CERT_PEM).unwrap()),
+        )
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_request(), "{:?}", err);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_host_tls_versions() {
+    use reqwest::TlsVersion;
+
+    let server = tls::https(tls::config());
+    let server = &server;
+    let version = |host: HostTlsConfig| async move {
+        let res = rustls_client()
+            .min_tls_version(TlsVersion::Tls1_3)
+            .tls_info(true)
+            .host_tls_config("localhost", host)
+            .build()
+            .unwrap()
+            .get(&server.url("/"))
+            .send()
+            .await
+            .unwrap();
+        res.tls_info().expect("tls info").protocol_version()
+    };
+
+    let host = HostTlsConfig::new()
+        .min_tls_version(TlsVersion::Tls1_2)
+        .max_tls_version(TlsVersion::Tls1_2);
+    assert_eq!(version(host).await, Some(TlsVersion::Tls1_2));
+    assert_eq!(version(HostTlsConfig::new()).await, Some(TlsVersion::Tls1_3));
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn host_tls_config_through_proxy() {
+    let server = tls::https(tls::config());
+    let proxy = tls::tunnel_proxy();
+
+    let res = Client::builder()
+        .use_rustls_tls()
+        .ip_family(IpFamily::V4Only)
+        .proxy(reqwest::Proxy::https(&format!("http://{}", proxy)).unwrap())
+        .host_tls_config(
+            "localhost",
+            HostTlsConfig::new()
+                .add_root_certificate(Certificate::from_pem(tls::CERT_PEM).unwrap()),
+        )
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+}
+
+#[cfg(feature = "default-tls")]
+#[tokio::test]
+async fn native_tls_host_tls_config() {
+    let server = tls::native_https();
+    let client = |pattern: &str| {
+        native_tls_client()
+            .host_tls_config(
+                pattern,
+                HostTlsConfig::new().danger_accept_invalid_certs(true),
+            )
+            .build()
+            .unwrap()
+    };
+
+    let res = client("localhost")
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    let err = client("example.com")
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_request(), "{:?}", err);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_sni() {
+    let server = tls::https(tls::config());
+
+    let res = rustls_client().build().unwrap().get(&server.url("/")).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    let res = rustls_client()
+        .tls_sni(false)
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    assert_eq!(server.sni_names(), [Some("localhost".to_owned()), None]);
+}
+
+#[cfg(feature = "default-tls")]
+#[tokio::test]
+async fn native_tls_sni() {
+    let server = tls::native_https();
+    let client = |sni: bool| {
+        native_tls_client()
+            .danger_accept_invalid_certs(true)
+            .tls_sni(sni)
+            .build()
+            .unwrap()
+    };
+
+    client(true).get(&server.url("/")).send().await.unwrap();
+    client(false).get(&server.url("/")).send().await.unwrap();
+    client(true)
+        .get(&format!("https://{}/", server.addr()))
+        .tls_server_name("localhost")
+        .send()
+        .await
+        .unwrap();
+
+    let localhost = Some("localhost".to_owned());
+    assert_eq!(server.sni_names(), [localhost.clone(), None, localhost]);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_server_name() {
+    let server = tls::https(tls::config());
+    let client = rustls_client().build().unwrap();
+    let url = format!("https://{}/", server.addr());
+
+    // rustls can't verify a certificate for an IP address.
+    client.get(&url).send().await.unwrap_err();
+
+    let res = client.get(&url).tls_server_name("localhost").send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    // The certificate isn't for this name.
+    let err = client
+        .get(&server.url("/"))
+        .tls_server_name("example.com")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_request(), "{:?}", err);
+
+    assert_eq!(server.sni_names()[0], Some("localhost".to_owned()));
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn tls_server_name_through_proxy() {
+    let server = tls::https(tls::config());
+    let proxy = tls::tunnel_proxy();
+
+    let res = rustls_client()
+        .proxy(reqwest::Proxy::https(&format!("http://{}", proxy)).unwrap())
+        .build()
+        .unwrap()
+        .get(&format!("https://{}/", server.addr()))
+        .tls_server_name("localhost")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+    assert_eq!(server.sni_names(), [Some("localhost".to_owned())]);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_http1_only() {
+    let server = tls::hyper_https(tls::config_with_h2());
+
+    let res = rustls_client().build().unwrap().get(&server.url("/")).send().await.unwrap();
+    assert_eq!(res.version(), reqwest::Version::HTTP_2);
+    assert_eq!(res.text().await.unwrap(), "HTTP/2.0");
+
+    let res = rustls_client()
+        .http1_only()
+        .build()
+        .unwrap()
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+    let alpn = res.connection_info().unwrap().alpn_protocol().map(<[u8]>::to_vec);
+    assert_eq!(alpn, Some(b"http/1.1".to_vec()));
+    assert_eq!(res.text().await.unwrap(), "HTTP/1.1");
+}
+
+// The h2 server is on rustls.
+#[cfg(all(feature = "native-tls", feature = "rustls-tls"))]
+#[tokio::test]
+async fn native_tls_alpn() {
+    let server = tls::hyper_https(tls::config_with_h2());
+    let client = |builder: reqwest::ClientBuilder| {
+        builder
+            .use_native_tls()
+            .ip_family(IpFamily::V4Only)
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap()
+    };
+
+    let res = client(Client::builder()).get(&server.url("/")).send().await.unwrap();
+    assert_eq!(res.version(), reqwest::Version::HTTP_2);
+    let alpn = res.connection_info().unwrap().alpn_protocol().map(<[u8]>::to_vec);
+    assert_eq!(alpn, Some(b"h2".to_vec()));
+    assert_eq!(res.text().await.unwrap(), "HTTP/2.0");
+
+    let res = client(Client::builder().http1_only())
+        .get(&server.url("/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.version(), reqwest::Version::HTTP_11);
+    assert_eq!(res.text().await.unwrap(), "HTTP/1.1");
+}
+
+// Without idle connections, each request opens a new connection.
+#[cfg(feature = "rustls-tls")]
+async fn resumed(client: &Client, server: &tls::Server) -> bool {
+    let res = client.get(&server.url("/")).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+    *server.resumed().last().unwrap()
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_session_resumption() {
+    let server = tls::https(tls::config());
+
+    let client = rustls_client().pool_max_idle_per_host(0).build().unwrap();
+    assert!(!resumed(&client, &server).await);
+    assert!(resumed(&client, &server).await);
+
+    let client = rustls_client()
+        .pool_max_idle_per_host(0)
+        .tls_session_resumption(false)
+        .build()
+        .unwrap();
+    assert!(!resumed(&client, &server).await);
+    assert!(!resumed(&client, &server).await);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_session_cache_shared() {
+    let server = tls::https(tls::config());
+    let cache = TlsSessionCache::new(8);
+
+    let client = |cache| {
+        rustls_client()
+            .pool_max_idle_per_host(0)
+            .tls_session_cache(cache)
+            .build()
+            .unwrap()
+    };
+    assert!(!resumed(&client(cache.clone()), &server).await);
+    assert!(resumed(&client(cache), &server).await);
+    assert!(!resumed(&client(TlsSessionCache::new(8)), &server).await);
+}
+
+#[cfg(feature = "rustls-tls")]
+#[tokio::test]
+async fn rustls_tls_early_data() {
+    let server = tls::https(tls::config());
+
+    let client = rustls_client()
+        .pool_max_idle_per_host(0)
+        .tls_early_data(true)
+        .build()
+        .unwrap();
+    assert!(!resumed(&client, &server).await);
+    assert!(resumed(&client, &server).await);
+
+    let res = client.post(&server.url("/")).body("data").send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+    assert_eq!(server.resumed(), [false, true, true]);
+}
+
+#[test]
+fn pin_certificate_invalid_hash() {
+    let err = Client::builder()
+        .pin_certificate("localhost", "sha256/not-a-hash")
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+}
+
+fn base64_zeros() -> String {
+    format!("sha256/{}", "A".repeat(43) + "=")
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "rustls-tls")]
+// `tls_key_log` reads `SSLKEYLOGFILE`, which this test sets, so it runs in a
+// binary of its own, without other tests reading the environment meanwhile.
+#[allow(unused)]
+mod support;
+use support::*;
+
+use reqwest::{Certificate, Client, IpFamily};
+
+#[tokio::test]
+async fn rustls_tls_key_log() {
+    let server = tls::https(tls::config());
+    let path = std::env::temp_dir().join(format!("reqwest-keylog-{}", std::process::id()));
+
+    std::env::set_var("SSLKEYLOGFILE", &path);
+    let client = Client::builder()
+        .use_rustls_tls()
+        .ip_family(IpFamily::V4Only)
+        .add_root_certificate(Certificate::from_pem(tls::CERT_PEM).unwrap())
+        .tls_key_log()
+        .build()
+        .unwrap();
+    std::env::remove_var("SSLKEYLOGFILE");
+
+    let res = client.get(&server.url("/")).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "Hello");
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let secret = log.lines().find(|line| line.starts_with("CLIENT_TRAFFIC_SECRET_0 "));
+    assert!(secret.is_some(), "{}", log);
+}
@@ -0,0 +1,173 @@
+#![cfg(feature = "tracing")]
+mod support;
+use support::*;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tracing_crate as tracing;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing_core::span::Current;
+use tracing::{Event, Metadata, Subscriber};
+
+type Span = (&'static Metadata<'static>, HashMap<String, String>);
+
+/// Collects the fields of every span, by span name.
+#[derive(Clone, Default)]
+struct Spans {
+    next_id: Arc<AtomicU64>,
+    spans: Arc<Mutex<HashMap<u64, Span>>>,
+    entered: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Spans {
+    fn named(&self, name: &str) -> Vec<HashMap<String, String>> {
+        let spans = self.spans.lock().unwrap();
+        let mut ids = spans.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        ids.into_iter()
+            .filter(|id| spans[id].0.name() == name)
+            .map(|id| spans[&id].1.clone())
+            .collect()
+    }
+}
+
+struct Fields<'a>(&'a mut HashMap<String, String>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for Spans {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut fields = HashMap::new();
+        attrs.record(&mut Fields(&mut fields));
+        self.spans
+            .lock()
+            .unwrap()
+            .insert(id, (attrs.metadata(), fields));
+        Id::from_u64(id)
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        let span = spans.get_mut(&id.into_u64()).unwrap();
+        values.record(&mut Fields(&mut span.1));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, id: &Id) {
+        self.entered.lock().unwrap().push(id.into_u64());
+    }
+
+    fn exit(&self, _: &Id) {
+        self.entered.lock().unwrap().pop();
+    }
+
+    fn current_span(&self) -> Current {
+        match self.entered.lock().unwrap().last() {
+            Some(&id) => Current::new(Id::from_u64(id), self.spans.lock().unwrap()[&id].0),
+            None => Current::none(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn request_spans() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |req| async move {
+        if req.uri() == "/start" {
+            http::Response::builder()
+                .status(302)
+                .header("location", "/end")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            http::Response::default()
+        }
+    });
+
+    let spans = Spans::default();
+    let _guard = tracing::subscriber::set_default(spans.clone());
+
+    let url = format!("http://{}/start", server.addr());
+    let res = reqwest::get(&url).await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+    let requests = spans.named("request");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["method"], "GET");
+    assert_eq!(requests[0]["url"], url);
+    assert_eq!(requests[0]["status"], "200");
+    assert_eq!(requests[0]["version"], "HTTP/1.1");
+    assert_eq!(requests[0]["remote_addr"], server.addr().to_string());
+
+    let redirects = spans.named("redirect");
+    assert_eq!(redirects.len(), 1);
+    assert_eq!(redirects[0]["url"], format!("http://{}/end", server.addr()));
+    assert_eq!(redirects[0]["status"], "302");
+
+    let connects = spans.named("connect");
+    assert!(!connects.is_empty());
+    assert_eq!(connects[0]["remote_addr"], server.addr().to_string());
+}
+
+#[tokio::test]
+async fn propagation_from_span() {
+    use reqwest::propagation::{Propagation, SpanContext, TraceContext};
+
+    let server = server::http(move |req| async move {
+        let traceparent = req.headers()["traceparent"].to_str().unwrap().to_owned();
+        if req.uri() == "/start" {
+            assert_eq!(
+                traceparent,
+                "00-0000000000000000000000000000002a-0000000000000001-01"
+            );
+            http::Response::builder()
+                .status(302)
+                .header("location", "/end")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            assert_eq!(
+                traceparent,
+                "00-0000000000000000000000000000002a-0000000000000002-01"
+            );
+            http::Response::default()
+        }
+    });
+
+    let spans = Spans::default();
+    let _guard = tracing::subscriber::set_default(spans.clone());
+
+    // the span of each hop gets its own span ID
+    let client = reqwest::Client::builder()
+        .propagation(Propagation::from_span(TraceContext, |span| {
+            let span_id = match span.metadata()?.name() {
+                "request" => 1,
+                "redirect" => 2,
+                _ => return None,
+            };
+            Some(SpanContext::new(42, span_id))
+        }))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/start", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}